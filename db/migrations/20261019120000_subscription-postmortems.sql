@@ -0,0 +1,9 @@
+-- migrate:up
+
+ALTER TABLE subscriptions
+  ADD COLUMN postmortems BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- migrate:down
+
+ALTER TABLE subscriptions
+  DROP COLUMN postmortems;
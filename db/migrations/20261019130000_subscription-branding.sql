@@ -0,0 +1,9 @@
+-- migrate:up
+
+ALTER TABLE subscriptions
+  ADD COLUMN branding JSONB;
+
+-- migrate:down
+
+ALTER TABLE subscriptions
+  DROP COLUMN branding;
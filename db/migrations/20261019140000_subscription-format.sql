@@ -0,0 +1,13 @@
+-- migrate:up
+
+CREATE TYPE message_format AS ENUM ('embed', 'text', 'both');
+
+ALTER TABLE subscriptions
+  ADD COLUMN format message_format NOT NULL DEFAULT 'embed';
+
+-- migrate:down
+
+ALTER TABLE subscriptions
+  DROP COLUMN format;
+
+DROP TYPE message_format;
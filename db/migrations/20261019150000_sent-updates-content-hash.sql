@@ -0,0 +1,9 @@
+-- migrate:up
+
+ALTER TABLE sent_updates
+  ADD COLUMN content_hash TEXT;
+
+-- migrate:down
+
+ALTER TABLE sent_updates
+  DROP COLUMN content_hash;
@@ -0,0 +1,14 @@
+-- migrate:up
+
+-- rows are inserted before a message is sent, and get their message id once
+-- it was delivered
+ALTER TABLE sent_updates
+  ALTER COLUMN message_id DROP NOT NULL;
+
+-- migrate:down
+
+DELETE FROM sent_updates
+  WHERE message_id IS NULL;
+
+ALTER TABLE sent_updates
+  ALTER COLUMN message_id SET NOT NULL;
@@ -0,0 +1,47 @@
+-- migrate:up
+
+CREATE TYPE sink_kind AS ENUM ('discord_bot', 'discord_webhook');
+
+-- where a subscription's messages are delivered, and anything that sink
+-- needs besides the channel and webhook
+ALTER TABLE subscriptions
+  ADD COLUMN sink sink_kind NOT NULL DEFAULT 'discord_bot',
+  ADD COLUMN sink_config jsonb;
+
+UPDATE subscriptions
+  SET sink = 'discord_webhook'
+  WHERE webhook_id IS NOT NULL;
+
+-- each sink identifies its messages differently, so they're stored as json.
+-- discord message ids are stored as strings. the unique constraint on the
+-- message id goes away, since the one on the incident update covers it
+ALTER TABLE sent_updates
+  ADD COLUMN message_ref jsonb;
+
+UPDATE sent_updates
+  SET message_ref = to_jsonb(message_id::text)
+  WHERE message_id IS NOT NULL;
+
+ALTER TABLE sent_updates
+  DROP CONSTRAINT sent_updates_subscription_id_message_id_incident_update_id_key,
+  DROP COLUMN message_id;
+
+-- migrate:down
+
+ALTER TABLE sent_updates
+  ADD COLUMN message_id bigint;
+
+UPDATE sent_updates
+  SET message_id = (message_ref #>> '{}')::bigint
+  WHERE message_ref IS NOT NULL;
+
+ALTER TABLE sent_updates
+  DROP COLUMN message_ref,
+  ADD CONSTRAINT sent_updates_subscription_id_message_id_incident_update_id_key
+    UNIQUE (subscription_id, message_id, incident_update_id);
+
+ALTER TABLE subscriptions
+  DROP COLUMN sink,
+  DROP COLUMN sink_config;
+
+DROP TYPE sink_kind;
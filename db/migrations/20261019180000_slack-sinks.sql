@@ -0,0 +1,24 @@
+-- migrate:up
+
+-- slack_webhook subscriptions have `{"webhook_url": ...}` in sink_config, and
+-- slack_bot ones have `{"channel": ...}`. slack messages are stored as
+-- `{"channel": ..., "ts": ...}`
+ALTER TYPE sink_kind ADD VALUE 'slack_bot';
+ALTER TYPE sink_kind ADD VALUE 'slack_webhook';
+
+-- migrate:down
+
+-- enum values can't be dropped, so the type is recreated without them
+DELETE FROM subscriptions
+  WHERE sink IN ('slack_bot', 'slack_webhook');
+
+ALTER TYPE sink_kind RENAME TO sink_kind_old;
+
+CREATE TYPE sink_kind AS ENUM ('discord_bot', 'discord_webhook');
+
+ALTER TABLE subscriptions
+  ALTER COLUMN sink DROP DEFAULT,
+  ALTER COLUMN sink TYPE sink_kind USING sink::text::sink_kind,
+  ALTER COLUMN sink SET DEFAULT 'discord_bot';
+
+DROP TYPE sink_kind_old;
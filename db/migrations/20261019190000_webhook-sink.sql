@@ -0,0 +1,26 @@
+-- migrate:up
+
+-- webhook subscriptions have `{"url": ..., "secret": ...}` in sink_config.
+-- their messages are stored as `{"id": ...}`, the first delivery's id
+ALTER TYPE sink_kind ADD VALUE 'webhook';
+
+-- migrate:down
+
+DELETE FROM subscriptions
+  WHERE sink = 'webhook';
+
+ALTER TYPE sink_kind RENAME TO sink_kind_old;
+
+CREATE TYPE sink_kind AS ENUM (
+  'discord_bot',
+  'discord_webhook',
+  'slack_bot',
+  'slack_webhook'
+);
+
+ALTER TABLE subscriptions
+  ALTER COLUMN sink DROP DEFAULT,
+  ALTER COLUMN sink TYPE sink_kind USING sink::text::sink_kind,
+  ALTER COLUMN sink SET DEFAULT 'discord_bot';
+
+DROP TYPE sink_kind_old;
@@ -0,0 +1,27 @@
+-- migrate:up
+
+-- matrix subscriptions have `{"room_id": ...}` in sink_config. their
+-- messages are stored as `{"room_id": ..., "event_id": ...}`
+ALTER TYPE sink_kind ADD VALUE 'matrix';
+
+-- migrate:down
+
+DELETE FROM subscriptions
+  WHERE sink = 'matrix';
+
+ALTER TYPE sink_kind RENAME TO sink_kind_old;
+
+CREATE TYPE sink_kind AS ENUM (
+  'discord_bot',
+  'discord_webhook',
+  'slack_bot',
+  'slack_webhook',
+  'webhook'
+);
+
+ALTER TABLE subscriptions
+  ALTER COLUMN sink DROP DEFAULT,
+  ALTER COLUMN sink TYPE sink_kind USING sink::text::sink_kind,
+  ALTER COLUMN sink SET DEFAULT 'discord_bot';
+
+DROP TYPE sink_kind_old;
@@ -0,0 +1,28 @@
+-- migrate:up
+
+-- email subscriptions have `{"recipients": [...]}` in sink_config. their
+-- messages are stored as `{"message_id": ...}`
+ALTER TYPE sink_kind ADD VALUE 'email';
+
+-- migrate:down
+
+DELETE FROM subscriptions
+  WHERE sink = 'email';
+
+ALTER TYPE sink_kind RENAME TO sink_kind_old;
+
+CREATE TYPE sink_kind AS ENUM (
+  'discord_bot',
+  'discord_webhook',
+  'slack_bot',
+  'slack_webhook',
+  'webhook',
+  'matrix'
+);
+
+ALTER TABLE subscriptions
+  ALTER COLUMN sink DROP DEFAULT,
+  ALTER COLUMN sink TYPE sink_kind USING sink::text::sink_kind,
+  ALTER COLUMN sink SET DEFAULT 'discord_bot';
+
+DROP TYPE sink_kind_old;
@@ -0,0 +1,17 @@
+-- migrate:up
+
+-- the latest version of every incident the service has processed, for the
+-- feeds. `impact` and `updated_at` are copied out of `data` for filtering
+-- and sorting
+CREATE TABLE incidents (
+  id text PRIMARY KEY,
+  data jsonb NOT NULL,
+  impact text NOT NULL,
+  updated_at timestamptz NOT NULL
+);
+
+CREATE INDEX incidents_updated_at_idx ON incidents (updated_at DESC);
+
+-- migrate:down
+
+DROP TABLE incidents;
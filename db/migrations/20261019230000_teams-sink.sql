@@ -0,0 +1,29 @@
+-- migrate:up
+
+-- teams subscriptions have `{"webhook_url": ...}` in sink_config. webhooks
+-- don't identify their messages, so they're stored as `{}`
+ALTER TYPE sink_kind ADD VALUE 'teams';
+
+-- migrate:down
+
+DELETE FROM subscriptions
+  WHERE sink = 'teams';
+
+ALTER TYPE sink_kind RENAME TO sink_kind_old;
+
+CREATE TYPE sink_kind AS ENUM (
+  'discord_bot',
+  'discord_webhook',
+  'slack_bot',
+  'slack_webhook',
+  'webhook',
+  'matrix',
+  'email'
+);
+
+ALTER TABLE subscriptions
+  ALTER COLUMN sink DROP DEFAULT,
+  ALTER COLUMN sink TYPE sink_kind USING sink::text::sink_kind,
+  ALTER COLUMN sink SET DEFAULT 'discord_bot';
+
+DROP TYPE sink_kind_old;
@@ -0,0 +1,33 @@
+-- migrate:up
+
+-- ntfy subscriptions have `{"topic": ..., "server_url"?: ..., "token"?: ...}`
+-- or a `username` and `password` instead of the token in sink_config. gotify
+-- ones have `{"server_url": ..., "token": ...}` with an application token.
+-- messages are stored as the `{"id": ...}` each service responds with
+ALTER TYPE sink_kind ADD VALUE 'ntfy';
+ALTER TYPE sink_kind ADD VALUE 'gotify';
+
+-- migrate:down
+
+DELETE FROM subscriptions
+  WHERE sink IN ('ntfy', 'gotify');
+
+ALTER TYPE sink_kind RENAME TO sink_kind_old;
+
+CREATE TYPE sink_kind AS ENUM (
+  'discord_bot',
+  'discord_webhook',
+  'slack_bot',
+  'slack_webhook',
+  'webhook',
+  'matrix',
+  'email',
+  'teams'
+);
+
+ALTER TABLE subscriptions
+  ALTER COLUMN sink DROP DEFAULT,
+  ALTER COLUMN sink TYPE sink_kind USING sink::text::sink_kind,
+  ALTER COLUMN sink SET DEFAULT 'discord_bot';
+
+DROP TYPE sink_kind_old;
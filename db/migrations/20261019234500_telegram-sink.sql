@@ -0,0 +1,34 @@
+-- migrate:up
+
+-- telegram subscriptions have `{"chat_id": ...}` in sink_config, with a
+-- numeric ID or a channel's `@username`, and optionally a
+-- `message_thread_id`. messages are stored as `{"chat_id": ..., "message_id":
+-- ...}`, always with the numeric chat ID
+ALTER TYPE sink_kind ADD VALUE 'telegram';
+
+-- migrate:down
+
+DELETE FROM subscriptions
+  WHERE sink = 'telegram';
+
+ALTER TYPE sink_kind RENAME TO sink_kind_old;
+
+CREATE TYPE sink_kind AS ENUM (
+  'discord_bot',
+  'discord_webhook',
+  'slack_bot',
+  'slack_webhook',
+  'webhook',
+  'matrix',
+  'email',
+  'teams',
+  'ntfy',
+  'gotify'
+);
+
+ALTER TABLE subscriptions
+  ALTER COLUMN sink DROP DEFAULT,
+  ALTER COLUMN sink TYPE sink_kind USING sink::text::sink_kind,
+  ALTER COLUMN sink SET DEFAULT 'discord_bot';
+
+DROP TYPE sink_kind_old;
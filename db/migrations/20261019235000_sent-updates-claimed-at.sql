@@ -0,0 +1,15 @@
+-- migrate:up
+
+-- when a row without a message was claimed, so claims left behind by a
+-- process that stopped mid-delivery can be taken over once they're old
+ALTER TABLE sent_updates
+  ADD COLUMN claimed_at timestamp with time zone;
+
+UPDATE sent_updates
+  SET claimed_at = created_at
+  WHERE message_ref IS NULL;
+
+-- migrate:down
+
+ALTER TABLE sent_updates
+  DROP COLUMN claimed_at;
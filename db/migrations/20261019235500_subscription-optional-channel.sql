@@ -0,0 +1,22 @@
+-- migrate:up
+
+-- only discord subscriptions belong to a server and channel. the unique
+-- constraints on them still allow one discord subscription per server and
+-- channel, and don't get in the way of other sinks since nulls never conflict
+ALTER TABLE subscriptions
+  ALTER COLUMN guild_id DROP NOT NULL,
+  ALTER COLUMN channel_id DROP NOT NULL,
+  ADD CONSTRAINT subscriptions_discord_channel_check CHECK (
+    sink NOT IN ('discord_bot', 'discord_webhook')
+    OR (guild_id IS NOT NULL AND channel_id IS NOT NULL)
+  );
+
+-- migrate:down
+
+DELETE FROM subscriptions
+  WHERE guild_id IS NULL OR channel_id IS NULL;
+
+ALTER TABLE subscriptions
+  DROP CONSTRAINT subscriptions_discord_channel_check,
+  ALTER COLUMN guild_id SET NOT NULL,
+  ALTER COLUMN channel_id SET NOT NULL;
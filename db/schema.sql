@@ -9,6 +9,36 @@ SET xmloption = content;
 SET client_min_messages = warning;
 SET row_security = off;
 
+--
+-- Name: message_format; Type: TYPE; Schema: public; Owner: -
+--
+
+CREATE TYPE public.message_format AS ENUM (
+    'embed',
+    'text',
+    'both'
+);
+
+
+--
+-- Name: sink_kind; Type: TYPE; Schema: public; Owner: -
+--
+
+CREATE TYPE public.sink_kind AS ENUM (
+    'discord_bot',
+    'discord_webhook',
+    'slack_bot',
+    'slack_webhook',
+    'webhook',
+    'matrix',
+    'email',
+    'teams',
+    'ntfy',
+    'gotify',
+    'telegram'
+);
+
+
 --
 -- Name: subscription_mode; Type: TYPE; Schema: public; Owner: -
 --
@@ -37,6 +67,18 @@ SET default_tablespace = '';
 
 SET default_table_access_method = heap;
 
+--
+-- Name: incidents; Type: TABLE; Schema: public; Owner: -
+--
+
+CREATE TABLE public.incidents (
+    id text NOT NULL,
+    data jsonb NOT NULL,
+    impact text NOT NULL,
+    updated_at timestamp with time zone NOT NULL
+);
+
+
 --
 -- Name: schema_migrations; Type: TABLE; Schema: public; Owner: -
 --
@@ -52,13 +94,15 @@ CREATE TABLE public.schema_migrations (
 
 CREATE TABLE public.sent_updates (
     id integer NOT NULL,
-    message_id bigint NOT NULL,
     mode public.subscription_mode NOT NULL,
     incident_id text NOT NULL,
     incident_update_id text NOT NULL,
     created_at timestamp with time zone DEFAULT now() NOT NULL,
     updated_at timestamp with time zone DEFAULT now() NOT NULL,
-    subscription_id integer NOT NULL
+    subscription_id integer NOT NULL,
+    content_hash text,
+    message_ref jsonb,
+    claimed_at timestamp with time zone
 );
 
 
@@ -88,14 +132,20 @@ ALTER SEQUENCE public.sent_updates_id_seq OWNED BY public.sent_updates.id;
 
 CREATE TABLE public.subscriptions (
     id integer NOT NULL,
-    guild_id bigint NOT NULL,
-    channel_id bigint NOT NULL,
+    guild_id bigint,
+    channel_id bigint,
     mode public.subscription_mode DEFAULT 'edit'::public.subscription_mode NOT NULL,
     role_pings bigint[] DEFAULT '{}'::bigint[] NOT NULL,
     webhook_id bigint,
     webhook_token text,
     created_at timestamp with time zone DEFAULT now() NOT NULL,
-    updated_at timestamp with time zone DEFAULT now() NOT NULL
+    updated_at timestamp with time zone DEFAULT now() NOT NULL,
+    postmortems boolean DEFAULT true NOT NULL,
+    branding jsonb,
+    format public.message_format DEFAULT 'embed'::public.message_format NOT NULL,
+    sink public.sink_kind DEFAULT 'discord_bot'::public.sink_kind NOT NULL,
+    sink_config jsonb,
+    CONSTRAINT subscriptions_discord_channel_check CHECK (((sink <> ALL (ARRAY['discord_bot'::public.sink_kind, 'discord_webhook'::public.sink_kind])) OR ((guild_id IS NOT NULL) AND (channel_id IS NOT NULL))))
 );
 
 
@@ -133,6 +183,14 @@ ALTER TABLE ONLY public.sent_updates ALTER COLUMN id SET DEFAULT nextval('public
 ALTER TABLE ONLY public.subscriptions ALTER COLUMN id SET DEFAULT nextval('public.subscriptions_id_seq'::regclass);
 
 
+--
+-- Name: incidents incidents_pkey; Type: CONSTRAINT; Schema: public; Owner: -
+--
+
+ALTER TABLE ONLY public.incidents
+    ADD CONSTRAINT incidents_pkey PRIMARY KEY (id);
+
+
 --
 -- Name: schema_migrations schema_migrations_pkey; Type: CONSTRAINT; Schema: public; Owner: -
 --
@@ -157,14 +215,6 @@ ALTER TABLE ONLY public.sent_updates
     ADD CONSTRAINT sent_updates_subscription_id_incident_id_incident_update_id_key UNIQUE (subscription_id, incident_id, incident_update_id);
 
 
---
--- Name: sent_updates sent_updates_subscription_id_message_id_incident_update_id_key; Type: CONSTRAINT; Schema: public; Owner: -
---
-
-ALTER TABLE ONLY public.sent_updates
-    ADD CONSTRAINT sent_updates_subscription_id_message_id_incident_update_id_key UNIQUE (subscription_id, message_id, incident_update_id);
-
-
 --
 -- Name: subscriptions subscriptions_channel_id_key; Type: CONSTRAINT; Schema: public; Owner: -
 --
@@ -197,6 +247,13 @@ ALTER TABLE ONLY public.subscriptions
     ADD CONSTRAINT subscriptions_webhook_id_webhook_token_key UNIQUE (webhook_id, webhook_token);
 
 
+--
+-- Name: incidents_updated_at_idx; Type: INDEX; Schema: public; Owner: -
+--
+
+CREATE INDEX incidents_updated_at_idx ON public.incidents USING btree (updated_at DESC);
+
+
 --
 -- Name: sent_updates set_timestamp; Type: TRIGGER; Schema: public; Owner: -
 --
@@ -229,4 +286,20 @@ ALTER TABLE ONLY public.sent_updates
 --
 
 INSERT INTO public.schema_migrations (version) VALUES
-    ('20221220055555');
+    ('20221220055555'),
+    ('20261019120000'),
+    ('20261019130000'),
+    ('20261019140000'),
+    ('20261019150000'),
+    ('20261019160000'),
+    ('20261019170000'),
+    ('20261019180000'),
+    ('20261019190000'),
+    ('20261019200000'),
+    ('20261019210000'),
+    ('20261019220000'),
+    ('20261019230000'),
+    ('20261019233000'),
+    ('20261019234500'),
+    ('20261019235000'),
+    ('20261019235500');
@@ -6,7 +6,7 @@
 //
 import {readFileSync} from 'node:fs';
 
-import {SubscriptionMode} from '@prisma/client';
+import {SinkKind, SubscriptionMode} from '@prisma/client';
 import {APIWebhook, Routes} from 'discord-api-types/v10';
 
 import {Client} from 'discord-status';
@@ -51,6 +51,7 @@ const main = async () => {
         createdAt: idToTimestamp(d._id.$oid),
         rolePings: d.config.roles.map(r => BigInt(r)),
         mode: modeToSubscriptionMode(d.config.mode),
+        sink: SinkKind.DiscordWebhook,
         webhookId: BigInt(d.webhook.id),
         webhookToken: d.webhook.token,
         sentUpdates: {
@@ -60,7 +61,8 @@ const main = async () => {
                 incidentId: u.incident,
                 incidentUpdateId: iu,
                 mode: modeToSubscriptionMode(d.config.mode),
-                messageId: BigInt(u.msg_id || 0),
+                // discord message ids are stored as strings
+                messageRef: u.msg_id || '0',
                 createdAt: idToTimestamp(d._id.$oid),
               }))
             )
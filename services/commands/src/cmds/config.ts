@@ -1,5 +1,5 @@
 import {DiscordAPIError} from '@discordjs/rest';
-import {SubscriptionMode} from '@prisma/client';
+import {MessageFormat, SinkKind, SubscriptionMode} from '@prisma/client';
 import {
   APIApplicationCommandInteractionDataChannelOption,
   APIApplicationCommandInteractionDataRoleOption,
@@ -93,6 +93,46 @@ export class ConfigCommand extends Command {
         },
       ],
     },
+    {
+      name: 'format',
+      description: 'How messages should be formatted',
+      type: ApplicationCommandOptionType.SubcommandGroup,
+      options: [
+        {
+          name: 'embed',
+          description: 'Send updates as embeds',
+          type: ApplicationCommandOptionType.Subcommand,
+        },
+        {
+          name: 'text',
+          description:
+            'Send updates as plain text, for channels bridged to other chat apps',
+          type: ApplicationCommandOptionType.Subcommand,
+        },
+        {
+          name: 'both',
+          description: 'Send updates as plain text with an embed',
+          type: ApplicationCommandOptionType.Subcommand,
+        },
+      ],
+    },
+    {
+      name: 'postmortems',
+      description: 'Whether incident postmortems should be posted',
+      type: ApplicationCommandOptionType.SubcommandGroup,
+      options: [
+        {
+          name: 'enable',
+          description: 'Post a summary when a postmortem is published',
+          type: ApplicationCommandOptionType.Subcommand,
+        },
+        {
+          name: 'disable',
+          description: 'Do not post postmortems',
+          type: ApplicationCommandOptionType.Subcommand,
+        },
+      ],
+    },
     {
       name: 'subscribe',
       description: 'Subscribe to status page updates',
@@ -131,6 +171,8 @@ export class ConfigCommand extends Command {
             mode: true,
             rolePings: true,
             webhookId: true,
+            format: true,
+            postmortems: true,
           },
         });
 
@@ -143,6 +185,8 @@ export class ConfigCommand extends Command {
           `>>> **Feed Channel:** <#${config.channelId}>`,
           config.webhookId && `**Webhook ID:** ${config.webhookId}`,
           `**Mode:** ${capitalize(config.mode)}`,
+          `**Format:** ${capitalize(config.format)}`,
+          `**Postmortems:** ${config.postmortems ? 'Enabled' : 'Disabled'}`,
           `**Role Pings:** ${
             config.rolePings.map(r => `<@&${r}>`).join(', ') || 'None'
           }`,
@@ -325,6 +369,108 @@ export class ConfigCommand extends Command {
         };
       }
 
+      case 'format': {
+        const opt = (
+          subcmd as APIApplicationCommandInteractionDataSubcommandGroupOption
+        ).options![0] as APIApplicationCommandInteractionDataSubcommandOption;
+
+        const current = await client.prisma.subscriptions.findFirst({
+          where: {
+            guildId: BigInt(i.guild_id!),
+          },
+          select: {
+            format: true,
+          },
+        });
+
+        if (!current) {
+          return notConfiguredResponse(client);
+        }
+
+        const newFormat = {
+          embed: MessageFormat.Embed,
+          text: MessageFormat.Text,
+          both: MessageFormat.Both,
+        }[opt.name]!;
+
+        if (newFormat === current.format) {
+          return {
+            type: InteractionResponseType.ChannelMessageWithSource,
+            data: {
+              content: `:x: Format is already set to \`${newFormat.toUpperCase()}\``,
+              flags: MessageFlags.Ephemeral,
+            },
+          };
+        }
+
+        await client.prisma.subscriptions.update({
+          data: {
+            format: newFormat,
+          },
+          where: {
+            guildId: BigInt(i.guild_id!),
+          },
+        });
+
+        return {
+          type: InteractionResponseType.ChannelMessageWithSource,
+          data: {
+            content: `:white_check_mark: Format is now set to \`${newFormat.toUpperCase()}\``,
+          },
+        };
+      }
+
+      case 'postmortems': {
+        const opt = (
+          subcmd as APIApplicationCommandInteractionDataSubcommandGroupOption
+        ).options![0] as APIApplicationCommandInteractionDataSubcommandOption;
+
+        const current = await client.prisma.subscriptions.findFirst({
+          where: {
+            guildId: BigInt(i.guild_id!),
+          },
+          select: {
+            postmortems: true,
+          },
+        });
+
+        if (!current) {
+          return notConfiguredResponse(client);
+        }
+
+        const enabled = opt.name === 'enable';
+
+        if (enabled === current.postmortems) {
+          return {
+            type: InteractionResponseType.ChannelMessageWithSource,
+            data: {
+              content: `:x: Postmortems are already ${
+                enabled ? 'enabled' : 'disabled'
+              }`,
+              flags: MessageFlags.Ephemeral,
+            },
+          };
+        }
+
+        await client.prisma.subscriptions.update({
+          data: {
+            postmortems: enabled,
+          },
+          where: {
+            guildId: BigInt(i.guild_id!),
+          },
+        });
+
+        return {
+          type: InteractionResponseType.ChannelMessageWithSource,
+          data: {
+            content: enabled
+              ? ':white_check_mark: A summary will be posted when an incident postmortem is published.'
+              : ':white_check_mark: Postmortems will no longer be posted.',
+          },
+        };
+      }
+
       case 'subscribe': {
         const channel = (
           subcmd as APIApplicationCommandInteractionDataSubcommandOption
@@ -406,6 +552,7 @@ export class ConfigCommand extends Command {
           data: {
             guildId: BigInt(i.guild_id!),
             channelId: BigInt(channel.value),
+            sink: SinkKind.DiscordBot,
           },
         });
 
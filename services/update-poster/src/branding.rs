@@ -0,0 +1,270 @@
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+use crate::{
+    config::read_toml,
+    constants::*,
+    error::ConfigError,
+    statuspage::{IncidentStatus, StatusIndicator},
+};
+
+/// How embeds and messages look. Loaded from a TOML file, and can be
+/// partially overridden per subscription with the `branding` JSON column.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Branding {
+    pub author: Author,
+    pub color_by: ColorBy,
+    pub colors: Colors,
+    pub emoji: EmojiSet,
+    pub buttons: Buttons,
+}
+
+impl Branding {
+    /// Loads branding from the TOML file at `path`, falling back to the
+    /// defaults if no path is given
+    pub fn load(path: Option<&str>) -> Result<Self, ConfigError> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+
+        read_toml(path)
+    }
+
+    /// Applies a subscription's branding overrides on top of this one. Any
+    /// field missing from `overrides` keeps its current value.
+    pub fn with_overrides(&self, overrides: Option<&Value>) -> Cow<'_, Self> {
+        let Some(overrides) = overrides else {
+            return Cow::Borrowed(self);
+        };
+
+        let mut merged =
+            serde_json::to_value(self).expect("branding is always valid json");
+        merge_json(&mut merged, overrides);
+
+        match serde_json::from_value(merged) {
+            Ok(branding) => Cow::Owned(branding),
+            Err(err) => {
+                warn!("Ignoring invalid branding overrides: {:#?}", err);
+                Cow::Borrowed(self)
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Author {
+    pub name: String,
+    pub icon_url: Option<String>,
+    pub url: Option<String>,
+}
+
+impl Default for Author {
+    fn default() -> Self {
+        Self {
+            name: "Discord Status".to_string(),
+            icon_url: Some(DEFAULT_ICON_URL.to_string()),
+            url: Some("https://discordstatus.com".to_string()),
+        }
+    }
+}
+
+/// Link buttons attached to messages. These are only sent when the message
+/// is posted by the bot or by a webhook owned by the bot's application.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Buttons {
+    pub enabled: bool,
+
+    /// Adds a "Subscribe to this incident" button, since the incident's page
+    /// is where the status page offers subscriptions to it
+    pub subscribe: bool,
+}
+
+impl Default for Buttons {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            subscribe: false,
+        }
+    }
+}
+
+/// Which palette is used to pick the colour of an embed
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorBy {
+    #[default]
+    Status,
+    Impact,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Colors {
+    pub status: StatusColors,
+    pub impact: ImpactColors,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct StatusColors {
+    pub investigating: u32,
+    pub identified: u32,
+    pub monitoring: u32,
+    pub resolved: u32,
+    pub postmortem: u32,
+}
+
+impl StatusColors {
+    pub fn get(&self, status: &IncidentStatus) -> u32 {
+        use IncidentStatus::*;
+
+        match status {
+            Investigating => self.investigating,
+            Identified => self.identified,
+            Monitoring => self.monitoring,
+            Resolved => self.resolved,
+            Postmortem => self.postmortem,
+        }
+    }
+}
+
+impl Default for StatusColors {
+    fn default() -> Self {
+        Self {
+            investigating: EMBED_ORANGE,
+            identified: EMBED_RED,
+            monitoring: EMBED_YELLOW,
+            resolved: EMBED_GREEN,
+            postmortem: EMBED_BLUE,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ImpactColors {
+    pub none: u32,
+    pub minor: u32,
+    pub major: u32,
+    pub critical: u32,
+    pub maintenance: u32,
+}
+
+impl ImpactColors {
+    pub fn get(&self, impact: &StatusIndicator) -> u32 {
+        use StatusIndicator::*;
+
+        match impact {
+            None => self.none,
+            Minor => self.minor,
+            Major => self.major,
+            Critical => self.critical,
+            Maintenance => self.maintenance,
+        }
+    }
+}
+
+impl Default for ImpactColors {
+    fn default() -> Self {
+        Self {
+            none: EMBED_GREEN,
+            minor: EMBED_YELLOW,
+            major: EMBED_ORANGE,
+            critical: EMBED_RED,
+            maintenance: EMBED_BLUE,
+        }
+    }
+}
+
+/// Emoji shown next to each update. Unset entries fall back to a unicode
+/// emoji, since custom emoji only render if the bot can use them.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct EmojiSet {
+    pub investigating: Option<String>,
+    pub identified: Option<String>,
+    pub monitoring: Option<String>,
+    pub resolved: Option<String>,
+    pub postmortem: Option<String>,
+}
+
+impl EmojiSet {
+    pub fn get(&self, status: &IncidentStatus) -> &str {
+        let (custom, fallback) = self.entry(status);
+
+        custom.unwrap_or(fallback)
+    }
+
+    /// Like [`EmojiSet::get`], but Discord's custom emoji are replaced with
+    /// the fallback, since they're just text like `<:name:id>` anywhere else
+    pub fn get_unicode(&self, status: &IncidentStatus) -> &str {
+        match self.entry(status) {
+            (Some(custom), _) if !is_custom_emoji(custom) => custom,
+            (_, fallback) => fallback,
+        }
+    }
+
+    fn entry(&self, status: &IncidentStatus) -> (Option<&str>, &'static str) {
+        use IncidentStatus::*;
+
+        let (custom, fallback) = match status {
+            Investigating => (&self.investigating, EMOJI_ORANGE),
+            Identified => (&self.identified, EMOJI_RED),
+            Monitoring => (&self.monitoring, EMOJI_YELLOW),
+            Resolved => (&self.resolved, EMOJI_GREEN),
+            Postmortem => (&self.postmortem, EMOJI_BLUE),
+        };
+
+        (custom.as_deref(), fallback)
+    }
+}
+
+/// Whether `emoji` is a Discord custom emoji, static or animated
+fn is_custom_emoji(emoji: &str) -> bool {
+    (emoji.starts_with("<:") || emoji.starts_with("<a:"))
+        && emoji.ends_with('>')
+}
+
+fn merge_json(base: &mut Value, overrides: &Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (k, v) in overrides {
+                merge_json(base.entry(k.clone()).or_insert(Value::Null), v);
+            }
+        },
+        (base, overrides) => *base = overrides.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_emoji_outside_discord() {
+        let emoji = EmojiSet {
+            investigating: Some("<:statusred:1234>".to_string()),
+            identified: Some("<a:spinning:1234>".to_string()),
+            monitoring: Some("👀".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            emoji.get(&IncidentStatus::Investigating),
+            "<:statusred:1234>"
+        );
+        assert_eq!(
+            emoji.get_unicode(&IncidentStatus::Investigating),
+            EMOJI_ORANGE
+        );
+        assert_eq!(emoji.get_unicode(&IncidentStatus::Identified), EMOJI_RED);
+        assert_eq!(emoji.get_unicode(&IncidentStatus::Monitoring), "👀");
+        assert_eq!(emoji.get_unicode(&IncidentStatus::Resolved), EMOJI_GREEN);
+    }
+}
@@ -0,0 +1,36 @@
+use twilight_model::channel::message::{
+    component::{ActionRow, Button, ButtonStyle},
+    Component,
+};
+
+use crate::{branding::Branding, statuspage::Incident, util::get_links};
+
+fn make_link_button(label: &str, url: &str) -> Component {
+    Component::Button(Button {
+        custom_id: None,
+        disabled: false,
+        emoji: None,
+        label: Some(label.to_string()),
+        style: ButtonStyle::Link,
+        url: Some(url.to_string()),
+    })
+}
+
+/// Builds the row of link buttons attached to an incident's messages
+pub fn make_link_buttons(
+    incident: &Incident,
+    branding: &Branding,
+) -> Vec<Component> {
+    let buttons: Vec<_> = get_links(incident, branding)
+        .into_iter()
+        .map(|(label, url)| make_link_button(label, &url))
+        .collect();
+
+    if buttons.is_empty() {
+        return vec![];
+    }
+
+    vec![Component::ActionRow(ActionRow {
+        components: buttons,
+    })]
+}
@@ -4,8 +4,11 @@ pub const EMBED_YELLOW: u32 = 0xF2EF42;
 pub const EMBED_GREEN: u32 = 0x43B581;
 pub const EMBED_BLUE: u32 = 0x4287F5;
 
-pub const EMOJI_RED: &str = "<:statusred:797222239661457478>";
-pub const EMOJI_ORANGE: &str = "<:statusorange:797222239979700263>";
-pub const EMOJI_YELLOW: &str = "<:statusyellow:797222239522390056>";
-pub const EMOJI_GREEN: &str = "<:statusgreen:797222239418187786>";
-pub const EMOJI_BLUE: &str = "<:statusblue:797222239942475786>";
+pub const EMOJI_RED: &str = "🔴";
+pub const EMOJI_ORANGE: &str = "🟠";
+pub const EMOJI_YELLOW: &str = "🟡";
+pub const EMOJI_GREEN: &str = "🟢";
+pub const EMOJI_BLUE: &str = "🔵";
+
+pub const DEFAULT_ICON_URL: &str =
+    "https://discord.com/assets/f9bb9c4af2b9c32a2c5ee0014661546d.png";
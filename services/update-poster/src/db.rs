@@ -1,20 +1,38 @@
+use serde::Serialize;
 use sqlx::{
     postgres::PgQueryResult,
-    types::time::OffsetDateTime,
+    types::{time::OffsetDateTime, Json, JsonValue},
     PgPool,
     Postgres,
     QueryBuilder,
 };
 
-use crate::error::Result;
+use crate::{error::Result, sinks::Destination, statuspage::Incident};
+
+/// How long a delivery can take before its claim is assumed to be left
+/// behind, long enough to outlast rate limits on a busy route
+pub const CLAIM_TIMEOUT_MINUTES: i32 = 15;
 
 pub struct Database {
     pg: PgPool,
+
+    /// Skips every write to `sent_updates` in dry-run mode
+    dry_run: bool,
 }
 
 impl Database {
-    pub fn new(pg_pool: PgPool) -> Self {
-        Self { pg: pg_pool }
+    pub fn new(pg_pool: PgPool, dry_run: bool) -> Self {
+        Self {
+            pg: pg_pool,
+            dry_run,
+        }
+    }
+
+    /// Checks that the database can be reached
+    pub async fn ping(&self) -> Result<()> {
+        sqlx::query!("SELECT 1 AS one").fetch_one(&self.pg).await?;
+
+        Ok(())
     }
 
     pub async fn get_guild_subscriptions(
@@ -72,17 +90,29 @@ impl Database {
                     s.id AS "subscription_id!",
                     s.mode AS "mode!: _",
                     s.role_pings AS "role_pings!",
-                    s.channel_id AS "channel_id!",
+                    s.channel_id AS "channel_id?",
                     s.webhook_id AS "webhook_id?",
-                    s.webhook_token AS "webhook_token?"
+                    s.webhook_token AS "webhook_token?",
+                    s.sink AS "sink!: _",
+                    s.sink_config AS "sink_config?",
+                    s.branding AS "branding?",
+                    s.format AS "format!: _"
                 FROM subscriptions AS s
                 LEFT JOIN sent_updates AS u
                     ON s.id = u.subscription_id
                     AND u.incident_id = $1
+                    -- claims left behind by a stopped process don't count,
+                    -- so they can be taken over
+                    AND NOT coalesce(
+                        u.message_ref IS NULL
+                        AND u.claimed_at < now() - make_interval(mins => $2),
+                        false
+                    )
                 WHERE u.incident_id IS NULL
                 GROUP BY s.id
             "#,
             incident_id,
+            CLAIM_TIMEOUT_MINUTES,
         )
         .fetch_all(&self.pg)
         .await
@@ -98,31 +128,47 @@ impl Database {
             SelectSubForUpdateCreated,
             r#"
                 SELECT
-                    s.channel_id as "channel_id!",
+                    s.channel_id as "channel_id?",
                     s.id as "subscription_id!",
                     s.mode as "mode!: _",
                     s.role_pings as "role_pings!",
                     s.webhook_id as "webhook_id?",
                     s.webhook_token as "webhook_token?",
-                    u.message_id as "message_id?"
+                    s.sink as "sink!: _",
+                    s.sink_config as "sink_config?",
+                    s.branding as "branding?",
+                    s.format as "format!: _",
+                    u.message_ref as "message_ref?",
+                    u.content_hash as "content_hash?"
                 FROM subscriptions AS s
                 LEFT JOIN (
                     SELECT DISTINCT ON (incident_id, subscription_id)
                         subscription_id,
-                        message_id
+                        message_ref,
+                        content_hash
                     FROM sent_updates
                     WHERE mode = 'edit'
                     AND incident_id = $1
+                    AND message_ref IS NOT NULL
+                    ORDER BY incident_id, subscription_id, updated_at DESC
                 ) AS u
                     ON u.subscription_id = s.id
                 LEFT JOIN sent_updates AS u2
-                   ON s.id = u2.subscription_id
-                   AND u2.incident_id = $1
-                   AND u2.incident_update_id = $2
+                    ON s.id = u2.subscription_id
+                    AND u2.incident_id = $1
+                    AND u2.incident_update_id = $2
+                    -- claims left behind by a stopped process don't count,
+                    -- so they can be taken over
+                    AND NOT coalesce(
+                        u2.message_ref IS NULL
+                        AND u2.claimed_at < now() - make_interval(mins => $3),
+                        false
+                    )
                 WHERE u2.incident_update_id IS NULL
             "#,
             incident_id,
             incident_update_id,
+            CLAIM_TIMEOUT_MINUTES,
         )
         .fetch_all(&self.pg)
         .await
@@ -138,20 +184,75 @@ impl Database {
             SelectSubsForUpdateModified,
             r#"
                 SELECT
-                    s.channel_id as "channel_id!",
+                    s.channel_id as "channel_id?",
                     s.id as "subscription_id!",
                     s.mode as "mode!: _",
+                    s.role_pings as "role_pings!",
                     s.webhook_id as "webhook_id?",
                     s.webhook_token as "webhook_token?",
-                    u.message_id as "message_id!"
+                    s.sink as "sink!: _",
+                    s.sink_config as "sink_config?",
+                    s.branding as "branding?",
+                    s.format as "format!: _",
+                    u.message_ref as "message_ref!"
                 FROM subscriptions AS s
                 INNER JOIN sent_updates AS u
                     ON s.id = u.subscription_id
                     AND u.incident_id = $1
                     AND u.incident_update_id = $2
+                WHERE u.message_ref IS NOT NULL
+            "#,
+            incident_id,
+            incident_update_id,
+        )
+        .fetch_all(&self.pg)
+        .await
+        .map_err(|e| e.into())
+    }
+
+    pub async fn get_postmortem_subscriptions(
+        &self,
+        incident_id: &String,
+        incident_update_id: &String,
+    ) -> Result<Vec<SelectSubsForPostmortem>> {
+        sqlx::query_as!(
+            SelectSubsForPostmortem,
+            r#"
+                SELECT
+                    s.id AS "subscription_id!",
+                    s.channel_id AS "channel_id?",
+                    s.webhook_id AS "webhook_id?",
+                    s.webhook_token AS "webhook_token?",
+                    s.sink AS "sink!: _",
+                    s.sink_config AS "sink_config?",
+                    s.branding AS "branding?",
+                    s.format AS "format!: _"
+                FROM subscriptions AS s
+                LEFT JOIN sent_updates AS u
+                    ON s.id = u.subscription_id
+                    AND u.incident_id = $1
+                    AND u.incident_update_id = $2
+                    -- claims left behind by a stopped process don't count,
+                    -- so they can be taken over
+                    AND NOT coalesce(
+                        u.message_ref IS NULL
+                        AND u.claimed_at < now() - make_interval(mins => $3),
+                        false
+                    )
+                WHERE s.postmortems
+                AND u.incident_update_id IS NULL
+                -- only subscriptions that were sent the incident, so ones
+                -- created after it don't get a postmortem out of nowhere
+                AND EXISTS (
+                    SELECT 1 FROM sent_updates AS prev
+                    WHERE prev.subscription_id = s.id
+                    AND prev.incident_id = $1
+                    AND prev.message_ref IS NOT NULL
+                )
             "#,
             incident_id,
             incident_update_id,
+            CLAIM_TIMEOUT_MINUTES,
         )
         .fetch_all(&self.pg)
         .await
@@ -162,22 +263,33 @@ impl Database {
         &self,
         data: CreateSentUpdate<'_>,
     ) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+
         sqlx::query!(
             r#"
                 INSERT INTO sent_updates (
-                    message_id,
+                    message_ref,
                     mode,
                     incident_id,
                     incident_update_id,
-                    subscription_id
+                    subscription_id,
+                    content_hash
                 )
-                VALUES ($1, $2, $3, $4, $5)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (subscription_id, incident_id, incident_update_id)
+                DO UPDATE SET
+                    message_ref = EXCLUDED.message_ref,
+                    content_hash = EXCLUDED.content_hash,
+                    updated_at = now()
             "#,
-            data.message_id,
+            data.message_ref,
             data.mode as SubscriptionMode,
             data.incident_id,
             data.incident_update_id,
             data.subscription_id,
+            data.content_hash,
         )
         .execute(&self.pg)
         .await?;
@@ -185,33 +297,307 @@ impl Database {
         Ok(())
     }
 
+    /// Records that messages were delivered, filling in the rows created by
+    /// [`Database::claim_sent_updates`]
     pub async fn create_many_sent_updates(
         &self,
         data: Vec<CreateSentUpdate<'_>>,
     ) -> Result<PgQueryResult> {
+        if data.is_empty() || self.dry_run {
+            return Ok(PgQueryResult::default());
+        }
+
         let mut qb: QueryBuilder<Postgres> = QueryBuilder::new(
             r#"
                 INSERT INTO sent_updates (
-                    message_id,
+                    message_ref,
                     mode,
                     incident_id,
                     incident_update_id,
-                    subscription_id
+                    subscription_id,
+                    content_hash
                 )
         "#,
         );
 
         qb.push_values(data, |mut b, d| {
-            b.push_bind(d.message_id)
+            b.push_bind(d.message_ref)
                 .push_bind(d.mode)
                 .push_bind(d.incident_id)
                 .push_bind(d.incident_update_id)
-                .push_bind(d.subscription_id);
+                .push_bind(d.subscription_id)
+                .push_bind(d.content_hash);
         });
 
+        qb.push(
+            r#"
+                ON CONFLICT (subscription_id, incident_id, incident_update_id)
+                DO UPDATE SET
+                    message_ref = EXCLUDED.message_ref,
+                    content_hash = EXCLUDED.content_hash,
+                    updated_at = now()
+            "#,
+        );
+
         qb.build().execute(&self.pg).await.map_err(|e| e.into())
     }
 
+    /// Records the intent to send an incident update to each subscription
+    /// before anything is sent, so it's never delivered twice even if the
+    /// process stops before the message ID is saved. Returns the
+    /// subscriptions that weren't already claimed and should be sent to.
+    /// Claims older than [`CLAIM_TIMEOUT_MINUTES`] that never got a message
+    /// were left behind by a process that stopped, and are taken over
+    pub async fn claim_sent_updates(
+        &self,
+        incident_id: &String,
+        incident_update_id: &String,
+        subscriptions: Vec<(i32, SubscriptionMode)>,
+    ) -> Result<Vec<i32>> {
+        if subscriptions.is_empty() {
+            return Ok(vec![]);
+        }
+
+        if self.dry_run {
+            return Ok(subscriptions.into_iter().map(|(id, _)| id).collect());
+        }
+
+        let mut qb: QueryBuilder<Postgres> = QueryBuilder::new(
+            r#"
+                INSERT INTO sent_updates (
+                    subscription_id,
+                    mode,
+                    incident_id,
+                    incident_update_id,
+                    claimed_at
+                )
+        "#,
+        );
+
+        qb.push_values(subscriptions, |mut b, (subscription_id, mode)| {
+            b.push_bind(subscription_id)
+                .push_bind(mode)
+                .push_bind(incident_id)
+                .push_bind(incident_update_id)
+                .push("now()");
+        });
+
+        qb.push(
+            r#"
+                ON CONFLICT (subscription_id, incident_id, incident_update_id)
+                DO UPDATE SET
+                    mode = EXCLUDED.mode,
+                    claimed_at = EXCLUDED.claimed_at,
+                    updated_at = now()
+                WHERE sent_updates.message_ref IS NULL
+                AND sent_updates.claimed_at < now() - make_interval(mins => "#,
+        )
+        .push_bind(CLAIM_TIMEOUT_MINUTES)
+        .push(
+            r#")
+                RETURNING subscription_id
+            "#,
+        );
+
+        let claimed: Vec<(i32,)> =
+            qb.build_query_as().fetch_all(&self.pg).await?;
+
+        Ok(claimed.into_iter().map(|(id,)| id).collect())
+    }
+
+    /// Removes the claims for deliveries that failed, so they can be sent
+    /// again
+    pub async fn release_sent_updates(
+        &self,
+        incident_update_id: &String,
+        subscription_ids: &[i32],
+    ) -> Result<()> {
+        if subscription_ids.is_empty() || self.dry_run {
+            return Ok(());
+        }
+
+        sqlx::query!(
+            r#"
+                DELETE FROM sent_updates
+                WHERE incident_update_id = $1
+                AND subscription_id = ANY($2)
+                AND message_ref IS NULL
+            "#,
+            incident_update_id,
+            subscription_ids,
+        )
+        .execute(&self.pg)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Gets the latest edit mode message sent to each subscription for an
+    /// incident
+    pub async fn get_incident_edit_messages(
+        &self,
+        incident_id: &String,
+    ) -> Result<Vec<SelectEditMessage>> {
+        sqlx::query_as!(
+            SelectEditMessage,
+            r#"
+                SELECT DISTINCT ON (u.subscription_id)
+                    s.id AS "subscription_id!",
+                    s.channel_id AS "channel_id?",
+                    s.role_pings AS "role_pings!",
+                    s.webhook_id AS "webhook_id?",
+                    s.webhook_token AS "webhook_token?",
+                    s.sink AS "sink!: _",
+                    s.sink_config AS "sink_config?",
+                    s.branding AS "branding?",
+                    s.format AS "format!: _",
+                    u.message_ref AS "message_ref!",
+                    u.content_hash AS "content_hash?"
+                FROM sent_updates AS u
+                INNER JOIN subscriptions AS s
+                    ON s.id = u.subscription_id
+                WHERE u.incident_id = $1
+                AND u.mode = 'edit'
+                AND u.message_ref IS NOT NULL
+                ORDER BY u.subscription_id, u.updated_at DESC, u.id DESC
+            "#,
+            incident_id,
+        )
+        .fetch_all(&self.pg)
+        .await
+        .map_err(|e| e.into())
+    }
+
+    /// Points every sent update for an incident at a message that replaced a
+    /// deleted one
+    pub async fn replace_message_ref(
+        &self,
+        subscription_id: i32,
+        incident_id: &String,
+        old_message_ref: &JsonValue,
+        new_message_ref: &JsonValue,
+    ) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+
+        sqlx::query!(
+            r#"
+                UPDATE sent_updates
+                SET message_ref = $4
+                WHERE subscription_id = $1
+                AND incident_id = $2
+                AND message_ref = $3
+            "#,
+            subscription_id,
+            incident_id,
+            old_message_ref,
+            new_message_ref,
+        )
+        .execute(&self.pg)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Records the content hash of a message after it was edited
+    pub async fn set_content_hash(
+        &self,
+        subscription_id: i32,
+        message_ref: &JsonValue,
+        content_hash: &String,
+    ) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+
+        sqlx::query!(
+            r#"
+                UPDATE sent_updates
+                SET content_hash = $3
+                WHERE subscription_id = $1
+                AND message_ref = $2
+            "#,
+            subscription_id,
+            message_ref,
+            content_hash,
+        )
+        .execute(&self.pg)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Saves the latest version of an incident for the feeds. Older versions
+    /// than the one saved are ignored
+    pub async fn save_incident(&self, incident: &Incident) -> Result<()> {
+        sqlx::query!(
+            r#"
+                INSERT INTO incidents (id, data, impact, updated_at)
+                VALUES (
+                    $1,
+                    $2::jsonb,
+                    $2::jsonb->>'impact',
+                    ($2::jsonb->>'updated_at')::timestamptz
+                )
+                ON CONFLICT (id) DO UPDATE SET
+                    data = EXCLUDED.data,
+                    impact = EXCLUDED.impact,
+                    updated_at = EXCLUDED.updated_at
+                WHERE incidents.updated_at <= EXCLUDED.updated_at
+            "#,
+            incident.id,
+            Json(incident) as _,
+        )
+        .execute(&self.pg)
+        .await?;
+
+        Ok(())
+    }
+
+    /// The most recently updated incidents, optionally only ones with one of
+    /// `impacts` or affecting one of `components`. Components are matched
+    /// by code or name, and everything is case-insensitive
+    pub async fn get_feed_incidents(
+        &self,
+        impacts: Option<&[String]>,
+        components: Option<&[String]>,
+        limit: i64,
+    ) -> Result<Vec<Incident>> {
+        let lowercase = |values: Option<&[String]>| {
+            values
+                .map(|v| v.iter().map(|v| v.to_lowercase()).collect::<Vec<_>>())
+        };
+        let impacts = lowercase(impacts);
+        let components = lowercase(components);
+
+        let rows = sqlx::query!(
+            r#"
+                SELECT data as "data: Json<Incident>"
+                FROM incidents
+                WHERE ($1::text[] IS NULL OR lower(impact) = ANY($1))
+                AND ($2::text[] IS NULL OR EXISTS (
+                    SELECT 1
+                    FROM jsonb_path_query(
+                        data,
+                        '$.incident_updates[*].affected_components[*]'
+                    ) c
+                    WHERE lower(c->>'code') = ANY($2)
+                    OR lower(c->>'name') = ANY($2)
+                ))
+                ORDER BY updated_at DESC
+                LIMIT $3
+            "#,
+            impacts.as_deref(),
+            components.as_deref(),
+            limit,
+        )
+        .fetch_all(&self.pg)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.data.0).collect())
+    }
+
     pub async fn create_subscription(
         &self,
         subscription: CreateSubscription,
@@ -248,25 +634,47 @@ impl Database {
     }
 }
 
-#[derive(Debug, sqlx::Type, Copy, Clone)]
+#[derive(Debug, Default, sqlx::Type, Copy, Clone, PartialEq)]
 #[sqlx(type_name = "subscription_mode", rename_all = "lowercase")]
 pub enum SubscriptionMode {
     Post,
+    #[default]
     Edit,
 }
 
-impl Default for SubscriptionMode {
-    fn default() -> Self {
-        Self::Edit
-    }
+/// Where a subscription's messages are delivered. See [`crate::sinks`]
+#[derive(Debug, sqlx::Type, Serialize, Copy, Clone, PartialEq, Eq, Hash)]
+#[sqlx(type_name = "sink_kind", rename_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum SinkKind {
+    DiscordBot,
+    DiscordWebhook,
+    SlackBot,
+    SlackWebhook,
+    Webhook,
+    Matrix,
+    Email,
+    Teams,
+    Ntfy,
+    Gotify,
+    Telegram,
+}
+
+#[derive(Debug, Default, sqlx::Type, Copy, Clone)]
+#[sqlx(type_name = "message_format", rename_all = "lowercase")]
+pub enum MessageFormat {
+    #[default]
+    Embed,
+    Text,
+    Both,
 }
 
 #[derive(Debug)]
 pub struct Subscription {
     pub id: i32,
 
-    pub guild_id: i64,
-    pub channel_id: i64,
+    pub guild_id: Option<i64>,
+    pub channel_id: Option<i64>,
 
     pub mode: SubscriptionMode,
     pub role_pings: Vec<i64>,
@@ -287,7 +695,7 @@ pub struct CreateSubscription {
 #[derive(Debug)]
 pub struct SentUpdate {
     pub id: i32,
-    pub message_id: i64,
+    pub message_ref: Option<JsonValue>,
     pub mode: SubscriptionMode,
     pub incident_id: String,
     pub incident_update_id: String,
@@ -296,11 +704,12 @@ pub struct SentUpdate {
 
 #[derive(Debug)]
 pub struct CreateSentUpdate<'a> {
-    pub message_id: i64,
+    pub message_ref: JsonValue,
     pub mode: SubscriptionMode,
     pub incident_id: &'a String,
     pub incident_update_id: &'a String,
     pub subscription_id: i32,
+    pub content_hash: String,
 }
 
 #[derive(Debug)]
@@ -308,28 +717,186 @@ pub struct SelectSubsForIncidentCreated {
     pub subscription_id: i32,
     pub mode: SubscriptionMode,
     pub role_pings: Vec<i64>,
-    pub channel_id: i64,
+    pub channel_id: Option<i64>,
     pub webhook_id: Option<i64>,
     pub webhook_token: Option<String>,
+    pub sink: SinkKind,
+    pub sink_config: Option<JsonValue>,
+    pub branding: Option<JsonValue>,
+    pub format: MessageFormat,
 }
 
 #[derive(Debug)]
 pub struct SelectSubForUpdateCreated {
-    pub channel_id: i64,
+    pub channel_id: Option<i64>,
     pub subscription_id: i32,
     pub mode: SubscriptionMode,
     pub role_pings: Vec<i64>,
     pub webhook_id: Option<i64>,
     pub webhook_token: Option<String>,
-    pub message_id: Option<i64>,
+    pub sink: SinkKind,
+    pub sink_config: Option<JsonValue>,
+    pub branding: Option<JsonValue>,
+    pub format: MessageFormat,
+    pub message_ref: Option<JsonValue>,
+    pub content_hash: Option<String>,
 }
 
 #[derive(Debug)]
 pub struct SelectSubsForUpdateModified {
     pub subscription_id: i32,
-    pub channel_id: i64,
+    pub channel_id: Option<i64>,
     pub mode: SubscriptionMode,
+    pub role_pings: Vec<i64>,
     pub webhook_id: Option<i64>,
     pub webhook_token: Option<String>,
-    pub message_id: i64,
+    pub sink: SinkKind,
+    pub sink_config: Option<JsonValue>,
+    pub branding: Option<JsonValue>,
+    pub format: MessageFormat,
+    pub message_ref: JsonValue,
+}
+
+#[derive(Debug)]
+pub struct SelectSubsForPostmortem {
+    pub subscription_id: i32,
+    pub channel_id: Option<i64>,
+    pub webhook_id: Option<i64>,
+    pub webhook_token: Option<String>,
+    pub sink: SinkKind,
+    pub sink_config: Option<JsonValue>,
+    pub branding: Option<JsonValue>,
+    pub format: MessageFormat,
+}
+
+#[derive(Debug)]
+pub struct SelectEditMessage {
+    pub subscription_id: i32,
+    pub channel_id: Option<i64>,
+    pub role_pings: Vec<i64>,
+    pub webhook_id: Option<i64>,
+    pub webhook_token: Option<String>,
+    pub sink: SinkKind,
+    pub sink_config: Option<JsonValue>,
+    pub branding: Option<JsonValue>,
+    pub format: MessageFormat,
+    pub message_ref: JsonValue,
+    pub content_hash: Option<String>,
+}
+
+/// Implements `destination()` for rows that select a subscription's sink
+macro_rules! impl_destination {
+    ($($row:ty),+) => {
+        $(
+            impl $row {
+                /// Where this subscription's messages are delivered
+                pub fn destination(&self) -> Destination {
+                    Destination {
+                        sink: self.sink,
+                        channel_id: self.channel_id,
+                        webhook_id: self.webhook_id,
+                        webhook_token: self.webhook_token.clone(),
+                        config: self.sink_config.clone(),
+                    }
+                }
+            }
+        )+
+    };
+}
+
+impl_destination!(
+    SelectSubsForIncidentCreated,
+    SelectSubForUpdateCreated,
+    SelectSubsForUpdateModified,
+    SelectSubsForPostmortem,
+    SelectEditMessage
+);
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use sqlx::postgres::PgPoolOptions;
+
+    use super::*;
+
+    /// Runs against the database in `DATABASE_URL`, which has to have the
+    /// migrations applied
+    #[tokio::test]
+    #[ignore = "needs a database"]
+    async fn expired_claims_are_retried() {
+        let pg = PgPoolOptions::new()
+            .connect(&std::env::var("DATABASE_URL").unwrap())
+            .await
+            .unwrap();
+        let db = Database::new(pg.clone(), false);
+
+        let unique = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as i64;
+        let incident_id = format!("test-{}", unique);
+        let update_id = format!("test-{}-update", unique);
+        let subscription = db
+            .create_subscription(CreateSubscription {
+                guild_id: unique,
+                channel_id: unique,
+                mode: None,
+                role_pings: None,
+            })
+            .await
+            .unwrap();
+        let claim = vec![(subscription.id, SubscriptionMode::Edit)];
+
+        let is_unsent = |created: Vec<SelectSubsForIncidentCreated>| {
+            created.iter().any(|s| s.subscription_id == subscription.id)
+        };
+
+        let claimed = db
+            .claim_sent_updates(&incident_id, &update_id, claim.clone())
+            .await
+            .unwrap();
+        assert_eq!(claimed, [subscription.id]);
+
+        // a claim that's still being delivered is left alone
+        let created = db
+            .get_incident_created_subscriptions(&incident_id)
+            .await
+            .unwrap();
+        assert!(!is_unsent(created));
+        let claimed = db
+            .claim_sent_updates(&incident_id, &update_id, claim.clone())
+            .await
+            .unwrap();
+        assert!(claimed.is_empty());
+
+        sqlx::query(
+            "UPDATE sent_updates SET claimed_at = now() - interval '1 hour'
+            WHERE subscription_id = $1",
+        )
+        .bind(subscription.id)
+        .execute(&pg)
+        .await
+        .unwrap();
+
+        let created = db
+            .get_incident_created_subscriptions(&incident_id)
+            .await
+            .unwrap();
+        assert!(is_unsent(created));
+        let update_created = db
+            .get_incident_update_created_subscriptions(&incident_id, &update_id)
+            .await
+            .unwrap();
+        assert!(update_created
+            .iter()
+            .any(|s| s.subscription_id == subscription.id));
+        let claimed = db
+            .claim_sent_updates(&incident_id, &update_id, claim)
+            .await
+            .unwrap();
+        assert_eq!(claimed, [subscription.id]);
+
+        db.delete_subscription(subscription.id).await.unwrap();
+    }
 }
@@ -0,0 +1,233 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use tracing::warn;
+use twilight_http::Client as DiscordRestClient;
+use twilight_model::{
+    channel::message::Component,
+    id::{
+        marker::{ApplicationMarker, MessageMarker},
+        Id,
+    },
+};
+
+use crate::{
+    error::{ApplicationError, Result},
+    message::MessageBody,
+};
+
+/// How long the result of checking the token is reused for
+const TOKEN_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+pub struct Discord {
+    rest: DiscordRestClient,
+    application_id: Id<ApplicationMarker>,
+
+    /// Whether each webhook was created by this application. Only those can
+    /// send message components.
+    webhook_owned: Mutex<HashMap<u64, bool>>,
+
+    /// When the token was last checked, and whether it was valid
+    token_check: Mutex<Option<(Instant, bool)>>,
+}
+
+impl Discord {
+    pub async fn new(rest: DiscordRestClient) -> Result<Self> {
+        let application =
+            rest.current_user_application().await?.model().await?;
+
+        Ok(Self {
+            rest,
+            application_id: application.id,
+            webhook_owned: Mutex::new(HashMap::new()),
+            token_check: Mutex::new(None),
+        })
+    }
+
+    /// Whether the token can still be used. Requests are only made once per
+    /// [`TOKEN_CHECK_INTERVAL`], since this is called by readiness probes
+    pub async fn is_token_valid(&self) -> bool {
+        let cached = *self.token_check.lock().unwrap();
+        if let Some((checked_at, valid)) = cached {
+            if checked_at.elapsed() < TOKEN_CHECK_INTERVAL {
+                return valid;
+            }
+        }
+
+        let valid = match self.rest.current_user().await {
+            Ok(_) => true,
+            Err(err) => {
+                warn!("Failed to check Discord token: {:#?}", err);
+                false
+            },
+        };
+
+        *self.token_check.lock().unwrap() = Some((Instant::now(), valid));
+
+        valid
+    }
+
+    /// Sends a message to a channel, through `webhook` if it's set
+    pub async fn create_message(
+        &self,
+        channel_id: i64,
+        webhook: Option<(i64, &str)>,
+        body: &MessageBody,
+    ) -> Result<Id<MessageMarker>> {
+        let components = self.get_components(webhook, &body.components).await;
+
+        let created_msg = if let Some((id, token)) = webhook {
+            self.rest
+                .execute_webhook(Id::new(id as u64), token)
+                .content(&body.content)?
+                .embeds(&body.embeds)?
+                .components(components)?
+                .wait()
+                .await
+                .map_err(|e| ApplicationError::MessageSendError {
+                    channel_id: channel_id as u64,
+                    webhook_id: Some(id as u64),
+                    error: e,
+                })
+        } else {
+            self.rest
+                .create_message(Id::new(channel_id as u64))
+                .content(&body.content)?
+                .embeds(&body.embeds)?
+                .components(components)?
+                .await
+                .map_err(|e| ApplicationError::MessageSendError {
+                    channel_id: channel_id as u64,
+                    webhook_id: None,
+                    error: e,
+                })
+        }?
+        .model()
+        .await
+        .map_err(ApplicationError::from)?;
+
+        Ok(created_msg.id)
+    }
+
+    pub async fn update_message(
+        &self,
+        channel_id: i64,
+        webhook: Option<(i64, &str)>,
+        message_id: Id<MessageMarker>,
+        body: &MessageBody,
+    ) -> Result<()> {
+        // twilight clears the content when given `None`
+        let content = Some(body.content.as_str()).filter(|c| !c.is_empty());
+        let components = self.get_components(webhook, &body.components).await;
+
+        if let Some((id, token)) = webhook {
+            self.rest
+                .update_webhook_message(Id::new(id as u64), token, message_id)
+                .content(content)?
+                .embeds(Some(&body.embeds))?
+                .components(Some(components))?
+                .await
+                .map_err(|e| ApplicationError::MessageEditError {
+                    channel_id: channel_id as u64,
+                    webhook_id: Some(id as u64),
+                    message_id: message_id.get(),
+                    error: e,
+                })?;
+        } else {
+            self.rest
+                .update_message(Id::new(channel_id as u64), message_id)
+                .content(content)?
+                .embeds(Some(&body.embeds))?
+                .components(Some(components))?
+                .await
+                .map_err(|e| ApplicationError::MessageEditError {
+                    channel_id: channel_id as u64,
+                    webhook_id: None,
+                    message_id: message_id.get(),
+                    error: e,
+                })?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn delete_message(
+        &self,
+        channel_id: i64,
+        webhook: Option<(i64, &str)>,
+        message_id: Id<MessageMarker>,
+    ) -> Result<()> {
+        let res = if let Some((id, token)) = webhook {
+            self.rest
+                .delete_webhook_message(Id::new(id as u64), token, message_id)
+                .await
+        } else {
+            self.rest
+                .delete_message(Id::new(channel_id as u64), message_id)
+                .await
+        };
+
+        res.map_err(|e| ApplicationError::MessageDeleteError {
+            channel_id: channel_id as u64,
+            webhook_id: webhook.map(|(id, _)| id as u64),
+            message_id: message_id.get(),
+            error: e,
+        })?;
+
+        Ok(())
+    }
+
+    /// Returns `components` if the message will be sent by the bot or by a
+    /// webhook owned by this application, otherwise nothing
+    async fn get_components<'a>(
+        &self,
+        webhook: Option<(i64, &str)>,
+        components: &'a [Component],
+    ) -> &'a [Component] {
+        let Some((id, token)) = webhook else {
+            return components;
+        };
+
+        if components.is_empty() {
+            return components;
+        }
+
+        let id = id as u64;
+        let cached = self.webhook_owned.lock().unwrap().get(&id).copied();
+
+        let owned = match cached {
+            Some(owned) => owned,
+            None => {
+                let webhook =
+                    match self.rest.webhook(Id::new(id)).token(token).await {
+                        Ok(res) => res.model().await.ok(),
+                        Err(err) => {
+                            warn!(
+                                webhook_id = id,
+                                "Failed to get webhook: {:#?}", err
+                            );
+                            None
+                        },
+                    };
+
+                let Some(webhook) = webhook else {
+                    return &[];
+                };
+
+                let owned = webhook.application_id == Some(self.application_id);
+                self.webhook_owned.lock().unwrap().insert(id, owned);
+
+                owned
+            },
+        };
+
+        if owned {
+            components
+        } else {
+            &[]
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use serde_json::json;
+use sqlx::types::JsonValue;
+use tracing::info;
+
+use crate::sinks::Destination;
+
+/// Takes the place of sending messages in dry-run mode. Rendered messages
+/// are written as JSON lines to a file, or logged if there isn't one.
+pub struct DryRunSink {
+    output: Option<Mutex<File>>,
+
+    /// Used to make up references for messages that would have been created
+    next_id: AtomicU64,
+}
+
+impl DryRunSink {
+    pub fn new(output: Option<&str>) -> io::Result<Self> {
+        let output = match output {
+            Some(path) => Some(Mutex::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            )),
+            None => None,
+        };
+
+        Ok(Self {
+            output,
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    /// Returns a made up reference for the message
+    pub fn create_message(
+        &self,
+        destination: &Destination,
+        payload: &JsonValue,
+        content_hash: &str,
+    ) -> JsonValue {
+        let message_ref =
+            json!(self.next_id.fetch_add(1, Ordering::Relaxed).to_string());
+        self.record(
+            "create",
+            destination,
+            &message_ref,
+            Some((payload, content_hash)),
+        );
+
+        message_ref
+    }
+
+    pub fn update_message(
+        &self,
+        destination: &Destination,
+        message_ref: &JsonValue,
+        payload: &JsonValue,
+        content_hash: &str,
+    ) {
+        self.record(
+            "update",
+            destination,
+            message_ref,
+            Some((payload, content_hash)),
+        );
+    }
+
+    pub fn delete_message(
+        &self,
+        destination: &Destination,
+        message_ref: &JsonValue,
+    ) {
+        self.record("delete", destination, message_ref, None);
+    }
+
+    /// Writes what would have been sent, as rendered by the destination's
+    /// sink
+    fn record(
+        &self,
+        action: &str,
+        destination: &Destination,
+        message_ref: &JsonValue,
+        payload: Option<(&JsonValue, &str)>,
+    ) {
+        let (payload, content_hash) = payload.unzip();
+        let entry = json!({
+            "action": action,
+            "sink": destination.sink,
+            "channel_id": destination.channel_id,
+            "webhook_id": destination.webhook_id,
+            "message_ref": message_ref,
+            "payload": payload,
+            "content_hash": content_hash,
+        });
+
+        let Some(output) = &self.output else {
+            info!(entry = entry.to_string(), "Dry run: {} message", action);
+            return;
+        };
+
+        let mut line = entry.to_string();
+        line.push('\n');
+
+        if let Err(err) = output.lock().unwrap().write_all(line.as_bytes()) {
+            tracing::error!("Failed to write dry run output: {:#?}", err);
+        }
+    }
+}
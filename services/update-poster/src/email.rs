@@ -0,0 +1,228 @@
+use serde::Serialize;
+
+use crate::{
+    branding::Branding,
+    embeds::POSTMORTEM_EXCERPT_MAX_LEN,
+    statuspage::{Incident, IncidentStatus, IncidentUpdate},
+    text::{
+        get_update_heading,
+        make_edit_text,
+        make_post_text,
+        make_postmortem_text,
+        make_update_html,
+        TextStyle,
+    },
+    util::{
+        escape_html,
+        fit_timeline,
+        get_embed_color,
+        get_excerpt,
+        get_formatted_utc_timestamp,
+        get_timeline_updates,
+    },
+};
+
+/// Emails don't have a length limit, but one incident shouldn't be able to
+/// make an enormous one
+const BODY_MAX_LEN: usize = 64000;
+
+/// A rendered email, without its sender and recipients.
+///
+/// Every email for an incident is threaded under the one for its first
+/// update, whether or not that one was sent. Mail clients group replies by
+/// their `References`, and most also need the subjects to match, so they're
+/// all the incident's name.
+#[derive(Clone, Debug, Serialize)]
+pub struct Email {
+    pub subject: String,
+    pub message_id: String,
+
+    /// The first update's `Message-ID`, for every other email
+    pub in_reply_to: Option<String>,
+    pub text: String,
+    pub html: String,
+}
+
+impl Email {
+    pub fn post(
+        incident: &Incident,
+        update: &IncidentUpdate,
+        branding: &Branding,
+        domain: &str,
+    ) -> Self {
+        Self::build(
+            incident,
+            update,
+            domain,
+            make_post_text(
+                incident,
+                update,
+                branding,
+                TextStyle::Plain,
+                BODY_MAX_LEN,
+            ),
+            make_html(
+                incident,
+                branding,
+                &update.status,
+                &make_update_html(update, branding, BODY_MAX_LEN),
+                "Started",
+            ),
+        )
+    }
+
+    /// The whole timeline, identified by its latest update
+    pub fn edit(
+        incident: &Incident,
+        branding: &Branding,
+        domain: &str,
+    ) -> Self {
+        let updates = get_timeline_updates(incident);
+        let Some(latest) = updates.first() else {
+            return Self::build_empty(incident, branding, domain);
+        };
+
+        let html = fit_timeline(
+            updates
+                .iter()
+                .map(|upd| make_update_html(upd, branding, BODY_MAX_LEN)),
+            BODY_MAX_LEN,
+        )
+        .concat();
+
+        Self::build(
+            incident,
+            latest,
+            domain,
+            make_edit_text(incident, branding, TextStyle::Plain, BODY_MAX_LEN),
+            make_html(incident, branding, &incident.status, &html, "Started"),
+        )
+    }
+
+    pub fn postmortem(
+        incident: &Incident,
+        update: &IncidentUpdate,
+        branding: &Branding,
+        domain: &str,
+    ) -> Self {
+        let html = format!(
+            "<p>{}<br>{}</p><p><a href=\"{}\">Read the full postmortem</a></p>",
+            get_update_heading(update, branding, TextStyle::Html),
+            escape_html(&get_excerpt(&update.body, POSTMORTEM_EXCERPT_MAX_LEN))
+                .replace('\n', "<br>"),
+            escape_html(&incident.shortlink),
+        );
+
+        Self::build(
+            incident,
+            update,
+            domain,
+            make_postmortem_text(
+                incident,
+                update,
+                branding,
+                TextStyle::Plain,
+                BODY_MAX_LEN,
+            ),
+            make_html(
+                incident,
+                branding,
+                &update.status,
+                &html,
+                "Postmortem published",
+            ),
+        )
+    }
+
+    fn build(
+        incident: &Incident,
+        update: &IncidentUpdate,
+        domain: &str,
+        text: String,
+        html: String,
+    ) -> Self {
+        let root = get_root_message_id(incident, domain);
+        let is_root = incident
+            .incident_updates
+            .iter()
+            .min_by_key(|u| u.created_at)
+            .is_some_and(|u| u.id == update.id);
+
+        let (subject, message_id, in_reply_to) = if is_root {
+            (incident.name.clone(), root, None)
+        } else {
+            (
+                format!("Re: {}", incident.name),
+                format!("<{}.{}@{}>", incident.id, update.id, domain),
+                Some(root),
+            )
+        };
+
+        Self {
+            subject,
+            message_id,
+            in_reply_to,
+            text,
+            html,
+        }
+    }
+
+    /// An incident with nothing but a postmortem, which the status page
+    /// doesn't produce in practice
+    fn build_empty(
+        incident: &Incident,
+        branding: &Branding,
+        domain: &str,
+    ) -> Self {
+        Self {
+            subject: incident.name.clone(),
+            message_id: get_root_message_id(incident, domain),
+            in_reply_to: None,
+            text: make_edit_text(
+                incident,
+                branding,
+                TextStyle::Plain,
+                BODY_MAX_LEN,
+            ),
+            html: make_html(
+                incident,
+                branding,
+                &incident.status,
+                "",
+                "Started",
+            ),
+        }
+    }
+}
+
+fn get_root_message_id(incident: &Incident, domain: &str) -> String {
+    format!("<{}@{}>", incident.id, domain)
+}
+
+/// Styles are inline, since most mail clients ignore `<style>` blocks
+fn make_html(
+    incident: &Incident,
+    branding: &Branding,
+    status: &IncidentStatus,
+    updates: &str,
+    footer: &str,
+) -> String {
+    format!(
+        concat!(
+            "<div style=\"border-left: 4px solid #{:06X}; padding-left: 12px\">",
+            "<h2><a href=\"{}\">{}</a></h2>",
+            "{}",
+            "<p style=\"color: #72767D; font-size: 12px\">{} · {} {} · ",
+            "<a href=\"{}\">View on status page</a></p>",
+            "</div>",
+        ),
+        get_embed_color(branding, incident, status),
+        escape_html(&incident.shortlink),
+        escape_html(&incident.name),
+        updates,
+        escape_html(&branding.author.name),
+        footer,
+        get_formatted_utc_timestamp(&incident.start_time()),
+        escape_html(&incident.shortlink),
+    )
+}
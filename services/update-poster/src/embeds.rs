@@ -1,5 +1,3 @@
-use std::env;
-
 use twilight_model::{
     channel::message::{
         embed::{EmbedAuthor, EmbedField, EmbedFooter},
@@ -9,31 +7,29 @@ use twilight_model::{
 };
 
 use crate::{
+    branding::Branding,
     statuspage::{Incident, IncidentUpdate},
     util::{
         get_embed_color,
+        get_excerpt,
         get_formatted_timestamp,
         get_status_emoji,
+        get_timeline_updates,
         truncate_with_ellipsis,
     },
 };
 
 const TITLE_MAX_LEN: usize = 256;
 const FIELD_VALUE_MAX_LEN: usize = 1024;
+pub const POSTMORTEM_EXCERPT_MAX_LEN: usize = 600;
 
-fn get_base_embed(incident: &Incident) -> Embed {
-    let color = get_embed_color(&incident.status);
+fn get_base_embed(incident: &Incident, branding: &Branding) -> Embed {
+    let color = get_embed_color(branding, incident, &incident.status);
 
     let author = EmbedAuthor {
-        name: "Discord Status".to_string(),
-        icon_url: Some(
-            "https://discord.com/assets/f9bb9c4af2b9c32a2c5ee0014661546d.png"
-                .to_string(),
-        ),
-        url: Some(
-            env::var("SUPPORT_SERVER")
-                .unwrap_or_else(|_| "https://discordstatus.com".to_string()),
-        ),
+        name: branding.author.name.clone(),
+        icon_url: branding.author.icon_url.clone(),
+        url: branding.author.url.clone(),
         proxy_icon_url: None,
     };
 
@@ -43,7 +39,7 @@ fn get_base_embed(incident: &Incident) -> Embed {
         proxy_icon_url: None,
     };
 
-    let embed_ts = Timestamp::from_secs(incident.created_at.timestamp())
+    let embed_ts = Timestamp::from_secs(incident.start_time().timestamp())
         .expect("Got invalid timstamp");
 
     let mut embed = Embed {
@@ -65,7 +61,7 @@ fn get_base_embed(incident: &Incident) -> Embed {
 
     if incident.name.len() > TITLE_MAX_LEN {
         embed.description = Some(format!("**{}**", incident.name.clone()));
-        embed.title = Some("Discord Status Update".to_string());
+        embed.title = Some(format!("{} Update", branding.author.name));
     } else {
         embed.title = Some(incident.name.clone());
     }
@@ -73,42 +69,39 @@ fn get_base_embed(incident: &Incident) -> Embed {
     embed
 }
 
-pub fn make_post_embed(incident: &Incident, update: &IncidentUpdate) -> Embed {
-    let emoji = get_status_emoji(&update.status);
-    let color = get_embed_color(&update.status);
-    // TODO: use display_at? -- seems to be a changable timestamp on the statuspage dashboard
-    let update_ts = get_formatted_timestamp(&update.created_at);
+pub fn make_post_embed(
+    incident: &Incident,
+    update: &IncidentUpdate,
+    branding: &Branding,
+) -> Embed {
+    let emoji = get_status_emoji(branding, &update.status);
+    let color = get_embed_color(branding, incident, &update.status);
+    let update_ts = get_formatted_timestamp(&update.display_time());
 
     let field = EmbedField {
-        name: format!(
-            "{} {} ({})",
-            emoji,
-            update.status.to_string(),
-            update_ts
-        ),
+        name: format!("{} {} ({})", emoji, update.status, update_ts),
         value: truncate_with_ellipsis(update.body.clone(), FIELD_VALUE_MAX_LEN),
         inline: false,
     };
 
-    let mut embed = get_base_embed(incident);
+    let mut embed = get_base_embed(incident, branding);
     embed.color = Some(color);
     embed.fields.push(field);
 
     embed
 }
 
-pub fn make_edit_embed(incident: &Incident) -> Embed {
-    let fields = incident
-        .incident_updates
-        .iter()
+pub fn make_edit_embed(incident: &Incident, branding: &Branding) -> Embed {
+    let fields = get_timeline_updates(incident)
+        .into_iter()
         .take(25)
         .rev()
         .map(|upd| {
-            let emoji = get_status_emoji(&upd.status);
-            let ts = get_formatted_timestamp(&upd.created_at);
+            let emoji = get_status_emoji(branding, &upd.status);
+            let ts = get_formatted_timestamp(&upd.display_time());
 
             EmbedField {
-                name: format!("{} {} ({})", emoji, upd.status.to_string(), ts),
+                name: format!("{} {} ({})", emoji, upd.status, ts),
                 value: truncate_with_ellipsis(
                     upd.body.clone(),
                     FIELD_VALUE_MAX_LEN,
@@ -118,8 +111,45 @@ pub fn make_edit_embed(incident: &Incident) -> Embed {
         })
         .collect::<Vec<_>>();
 
-    let mut embed = get_base_embed(incident);
+    let mut embed = get_base_embed(incident, branding);
     embed.fields = fields;
 
     embed
 }
+
+pub fn make_postmortem_embed(
+    incident: &Incident,
+    update: &IncidentUpdate,
+    branding: &Branding,
+) -> Embed {
+    let emoji = get_status_emoji(branding, &update.status);
+    let excerpt = get_excerpt(&update.body, POSTMORTEM_EXCERPT_MAX_LEN);
+
+    let field = EmbedField {
+        name: format!(
+            "{} {} ({})",
+            emoji,
+            update.status,
+            get_formatted_timestamp(&update.display_time()),
+        ),
+        value: format!(
+            "{}\n\n[Read the full postmortem]({})",
+            excerpt, incident.shortlink
+        ),
+        inline: false,
+    };
+
+    let mut embed = get_base_embed(incident, branding);
+    embed.color = Some(get_embed_color(branding, incident, &update.status));
+    embed.fields.push(field);
+
+    if let Some(footer) = embed.footer.as_mut() {
+        footer.text = "Postmortem published".to_string();
+    }
+    embed.timestamp = Some(
+        Timestamp::from_secs(update.display_time().timestamp())
+            .expect("Got invalid timstamp"),
+    );
+
+    embed
+}
@@ -1,5 +1,17 @@
+use std::time::Duration;
+
 use thiserror::Error;
-use twilight_http::response::DeserializeBodyError;
+use twilight_http::{
+    api_error::{ApiError, GeneralApiError},
+    error::ErrorType,
+    response::DeserializeBodyError,
+};
+use twilight_validate::message::MessageValidationError;
+
+use crate::db::SinkKind;
+
+/// https://discord.com/developers/docs/topics/opcodes-and-status-codes#json
+const UNKNOWN_MESSAGE: u64 = 10008;
 
 #[derive(Debug, Error)]
 pub enum ApplicationError {
@@ -24,12 +36,68 @@ pub enum ApplicationError {
         error: twilight_http::Error,
     },
 
+    #[error("failed to delete message {}/{}: {:?} (webhook: {:?})", .channel_id, .message_id, .error, .webhook_id)]
+    MessageDeleteError {
+        channel_id: u64,
+        message_id: u64,
+        webhook_id: Option<u64>,
+        error: twilight_http::Error,
+    },
+
+    #[error("message can't be sent: {}", .source)]
+    InvalidMessage {
+        #[from]
+        source: MessageValidationError,
+    },
+
     #[error("failed to deserialize response body")]
     DeserializeBodyError {
         #[from]
         source: DeserializeBodyError,
     },
 
+    #[error("not sending messages while shutting down")]
+    ShuttingDown,
+
+    #[error("no {:?} sink is configured", .sink)]
+    SinkNotConfigured { sink: SinkKind },
+
+    #[error("invalid {:?} subscription: {}", .sink, .reason)]
+    InvalidDestination { sink: SinkKind, reason: String },
+
+    #[error("{:?} request failed: {}", .sink, .source)]
+    SinkRequestError {
+        sink: SinkKind,
+        source: reqwest::Error,
+    },
+
+    #[error("{:?} returned an error: {}", .sink, .error)]
+    SinkApiError { sink: SinkKind, error: String },
+
+    #[error("{:?} had a server error: {}", .sink, .error)]
+    SinkServerError { sink: SinkKind, error: String },
+
+    #[error("{:?} is rate limited for {:?}", .sink, .retry_after)]
+    SinkRatelimited {
+        sink: SinkKind,
+        retry_after: Duration,
+    },
+
+    #[error("{:?} message no longer exists", .sink)]
+    MessageNotFound { sink: SinkKind },
+
+    #[error("{:?} can't {} messages", .sink, .action)]
+    Unsupported {
+        sink: SinkKind,
+        action: &'static str,
+    },
+
+    #[error("invalid message reference: {}", .source)]
+    MessageRefError {
+        #[from]
+        source: serde_json::Error,
+    },
+
     #[error("database query failed: {:?}", .source)]
     SqlxError {
         #[from]
@@ -37,4 +105,84 @@ pub enum ApplicationError {
     },
 }
 
+impl ApplicationError {
+    /// Returns whether the rate limit was global and how long to wait, if
+    /// this error was caused by hitting a rate limit
+    pub fn ratelimit(&self) -> Option<(bool, Duration)> {
+        if let Self::SinkRatelimited { retry_after, .. } = self {
+            return Some((false, *retry_after));
+        }
+
+        match self.api_error()? {
+            ApiError::Ratelimited(r) => {
+                Some((r.global, Duration::from_secs_f64(r.retry_after)))
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns whether the message being edited no longer exists
+    pub fn is_unknown_message(&self) -> bool {
+        if let Self::MessageNotFound { .. } = self {
+            return true;
+        }
+
+        matches!(
+            self.api_error(),
+            Some(ApiError::General(GeneralApiError {
+                code: UNKNOWN_MESSAGE,
+                ..
+            }))
+        )
+    }
+
+    fn api_error(&self) -> Option<&ApiError> {
+        let error = match self {
+            Self::TwilightHTTPError { source } => source,
+            Self::MessageSendError { error, .. } => error,
+            Self::MessageEditError { error, .. } => error,
+            Self::MessageDeleteError { error, .. } => error,
+            _ => return None,
+        };
+
+        match error.kind() {
+            ErrorType::Response { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
 pub type Result<T> = std::result::Result<T, ApplicationError>;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("failed to read config file {}: {}", .path, .source)]
+    ReadError {
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("failed to parse config file {}: {}", .path, .source)]
+    ParseError {
+        path: String,
+        source: toml::de::Error,
+    },
+
+    #[error("invalid value for environment variable {}: {}", .name, .reason)]
+    EnvError { name: &'static str, reason: String },
+
+    #[error("invalid config option {}: {}", .field, .reason)]
+    InvalidError { field: &'static str, reason: String },
+}
+
+#[derive(Debug, Error)]
+pub enum SourceError {
+    #[error("failed to fetch incidents: {}", .source)]
+    RequestError {
+        #[from]
+        source: reqwest::Error,
+    },
+
+    #[error("no recorded incidents left to replay")]
+    ReplayFinished,
+}
@@ -0,0 +1,154 @@
+//! Atom and RSS feeds of incident updates. Every update is its own entry, so
+//! feed readers show new updates the same way subscriptions in post mode do.
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    statuspage::{Incident, IncidentUpdate},
+    util::escape_html,
+};
+
+/// How many entries a feed has at most, newest first
+const MAX_ENTRIES: usize = 100;
+
+/// What a feed is called and where it links to
+pub struct FeedInfo<'a> {
+    pub title: &'a str,
+    pub link: &'a str,
+}
+
+struct Entry<'a> {
+    incident: &'a Incident,
+    update: &'a IncidentUpdate,
+}
+
+impl Entry<'_> {
+    /// Stays the same when the update is edited, so feed readers update the
+    /// entry instead of showing it again
+    fn id(&self) -> String {
+        format!(
+            "urn:discord-status:incident:{}:update:{}",
+            self.incident.id, self.update.id
+        )
+    }
+
+    fn title(&self) -> String {
+        format!("{}: {}", self.incident.name, self.update.status)
+    }
+}
+
+pub fn make_atom(info: &FeedInfo, incidents: &[Incident]) -> String {
+    let entries = get_entries(incidents);
+
+    let mut xml = format!(
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n",
+            "<feed xmlns=\"http://www.w3.org/2005/Atom\">\n",
+            "<id>urn:discord-status:feed</id>\n",
+            "<title>{}</title>\n",
+            "<link href=\"{}\"/>\n",
+            "<author><name>{}</name></author>\n",
+            "<updated>{}</updated>\n",
+        ),
+        escape_html(info.title),
+        escape_html(info.link),
+        escape_html(info.title),
+        get_updated(&entries).to_rfc3339(),
+    );
+
+    for entry in &entries {
+        xml += &format!(
+            concat!(
+                "<entry>\n",
+                "<id>{}</id>\n",
+                "<title>{}</title>\n",
+                "<link href=\"{}\"/>\n",
+                "<published>{}</published>\n",
+                "<updated>{}</updated>\n",
+                "<category term=\"{}\"/>\n",
+                "<content type=\"text\">{}</content>\n",
+                "</entry>\n",
+            ),
+            entry.id(),
+            escape_html(&entry.title()),
+            escape_html(&entry.incident.shortlink),
+            entry.update.display_time().to_rfc3339(),
+            entry.update.updated_at.to_rfc3339(),
+            entry.incident.impact.as_str(),
+            escape_html(&entry.update.body),
+        );
+    }
+
+    xml + "</feed>\n"
+}
+
+pub fn make_rss(info: &FeedInfo, incidents: &[Incident]) -> String {
+    let entries = get_entries(incidents);
+
+    let mut xml = format!(
+        concat!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n",
+            "<rss version=\"2.0\">\n",
+            "<channel>\n",
+            "<title>{}</title>\n",
+            "<link>{}</link>\n",
+            "<description>Incident updates from {}</description>\n",
+            "<lastBuildDate>{}</lastBuildDate>\n",
+        ),
+        escape_html(info.title),
+        escape_html(info.link),
+        escape_html(info.title),
+        get_updated(&entries).to_rfc2822(),
+    );
+
+    for entry in &entries {
+        xml += &format!(
+            concat!(
+                "<item>\n",
+                "<guid isPermaLink=\"false\">{}</guid>\n",
+                "<title>{}</title>\n",
+                "<link>{}</link>\n",
+                "<pubDate>{}</pubDate>\n",
+                "<category>{}</category>\n",
+                "<description>{}</description>\n",
+                "</item>\n",
+            ),
+            entry.id(),
+            escape_html(&entry.title()),
+            escape_html(&entry.incident.shortlink),
+            entry.update.display_time().to_rfc2822(),
+            entry.incident.impact.as_str(),
+            escape_html(&entry.update.body),
+        );
+    }
+
+    xml + "</channel>\n</rss>\n"
+}
+
+/// Every update of every incident, newest first
+fn get_entries(incidents: &[Incident]) -> Vec<Entry<'_>> {
+    let mut entries: Vec<_> = incidents
+        .iter()
+        .flat_map(|incident| {
+            incident
+                .incident_updates
+                .iter()
+                .map(move |update| Entry { incident, update })
+        })
+        .collect();
+
+    entries.sort_by_key(|e| std::cmp::Reverse(e.update.display_time()));
+    entries.truncate(MAX_ENTRIES);
+
+    entries
+}
+
+/// When the feed last changed. Feeds always need a time, so an empty one
+/// uses the current time
+fn get_updated(entries: &[Entry]) -> DateTime<Utc> {
+    entries
+        .iter()
+        .map(|e| e.update.updated_at)
+        .max()
+        .unwrap_or_else(Utc::now)
+}
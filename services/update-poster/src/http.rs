@@ -0,0 +1,190 @@
+use std::{future::Future, net::SocketAddr, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use tracing::info;
+
+use crate::{
+    branding::Branding,
+    db::Database,
+    discord::Discord,
+    feed::{make_atom, make_rss, FeedInfo},
+    metrics::Metrics,
+    statuspage::Incident,
+};
+
+/// How many poll intervals can pass without a successful poll before the
+/// service isn't ready anymore
+const MAX_MISSED_POLLS: u32 = 3;
+
+/// How many of the most recently updated incidents the feeds are built from
+const FEED_INCIDENTS: i64 = 50;
+
+pub struct HttpState {
+    pub db: Arc<Database>,
+    pub discord: Arc<Discord>,
+    pub metrics: Arc<Metrics>,
+
+    /// How often the status page is polled
+    pub poll_interval: Duration,
+
+    /// The feeds are named after the author and link to the status page
+    pub branding: Arc<Branding>,
+    pub statuspage_url: String,
+}
+
+/// Filters for the feeds. Each is a comma separated list, and an incident
+/// only has to match one of its values
+#[derive(Deserialize)]
+struct FeedQuery {
+    /// Component codes or names, like `api,media proxy`
+    component: Option<String>,
+
+    /// Impacts, like `major,critical`
+    impact: Option<String>,
+}
+
+/// Serves the health check, metrics and feed endpoints until `shutdown`
+/// completes
+pub async fn serve(
+    addr: SocketAddr,
+    state: HttpState,
+    shutdown: impl Future<Output = ()>,
+) {
+    let app = Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics))
+        .route("/feed.atom", get(atom_feed))
+        .route("/feed.rss", get(rss_feed))
+        .with_state(Arc::new(state));
+
+    let server = match axum::Server::try_bind(&addr) {
+        Ok(server) => server,
+        Err(err) => {
+            tracing::error!("Failed to bind HTTP server: {:#?}", err);
+            return;
+        },
+    };
+
+    info!(
+        addr = addr.to_string(),
+        "Serving health checks, metrics and feeds"
+    );
+
+    if let Err(err) = server
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(shutdown)
+        .await
+    {
+        tracing::error!("HTTP server failed: {:#?}", err);
+    }
+}
+
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+async fn readyz(State(state): State<Arc<HttpState>>) -> impl IntoResponse {
+    let postgres = state.db.ping().await.is_ok();
+
+    let max_age =
+        chrono::Duration::from_std(state.poll_interval * MAX_MISSED_POLLS)
+            .unwrap();
+    let statuspage = state
+        .metrics
+        .last_poll()
+        .is_some_and(|t| Utc::now().signed_duration_since(t) < max_age);
+
+    let discord = state.discord.is_token_valid().await;
+
+    let status = if postgres && statuspage && discord {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    let check = |ok| if ok { "ok" } else { "failing" };
+    let body = format!(
+        "postgres: {}\nstatuspage: {}\ndiscord: {}\n",
+        check(postgres),
+        check(statuspage),
+        check(discord),
+    );
+
+    (status, body)
+}
+
+async fn metrics(State(state): State<Arc<HttpState>>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.encode(),
+    )
+}
+
+async fn atom_feed(
+    State(state): State<Arc<HttpState>>,
+    Query(query): Query<FeedQuery>,
+) -> impl IntoResponse {
+    let incidents = get_feed_incidents(&state, &query).await?;
+
+    Ok::<_, StatusCode>((
+        [(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+        make_atom(&feed_info(&state), &incidents),
+    ))
+}
+
+async fn rss_feed(
+    State(state): State<Arc<HttpState>>,
+    Query(query): Query<FeedQuery>,
+) -> impl IntoResponse {
+    let incidents = get_feed_incidents(&state, &query).await?;
+
+    Ok::<_, StatusCode>((
+        [(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8")],
+        make_rss(&feed_info(&state), &incidents),
+    ))
+}
+
+fn feed_info(state: &HttpState) -> FeedInfo<'_> {
+    FeedInfo {
+        title: &state.branding.author.name,
+        link: &state.statuspage_url,
+    }
+}
+
+async fn get_feed_incidents(
+    state: &HttpState,
+    query: &FeedQuery,
+) -> Result<Vec<Incident>, StatusCode> {
+    let split = |list: &Option<String>| {
+        list.as_ref().map(|list| {
+            list.split(',')
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .collect::<Vec<_>>()
+        })
+    };
+    let impacts = split(&query.impact);
+    let components = split(&query.component);
+
+    state
+        .db
+        .get_feed_incidents(
+            impacts.as_deref(),
+            components.as_deref(),
+            FEED_INCIDENTS,
+        )
+        .await
+        .map_err(|err| {
+            tracing::error!("Failed to get feed incidents: {:#?}", err);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
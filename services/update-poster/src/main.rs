@@ -1,42 +1,102 @@
+pub mod branding;
+pub mod components;
+pub mod config;
 pub mod constants;
 pub mod db;
+pub mod discord;
+pub mod dry_run;
+pub mod email;
 pub mod embeds;
 pub mod error;
+pub mod feed;
+pub mod http;
+pub mod matrix;
+pub mod message;
+pub mod metrics;
+pub mod mqtt;
+pub mod push;
+pub mod reconciler;
+pub mod replay;
+pub mod scheduler;
+pub mod sinks;
+pub mod slack;
 pub mod statuspage;
+pub mod teams;
+pub mod telegram;
+pub mod text;
 pub mod util;
+pub mod webhook;
 
-use std::env;
+use std::sync::Arc;
 
-use embeds::{make_edit_embed, make_post_embed};
-use futures::{future::join_all, StreamExt};
+use futures::StreamExt;
+use rumqttc::MqttOptions;
 use sqlx::postgres::PgPoolOptions;
-use tokio::sync::broadcast;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::{broadcast, oneshot},
+    time,
+};
 use tracing::{info, warn};
 use twilight_http::Client as DiscordRestClient;
-use twilight_model::{
-    channel::message::Embed,
-    id::{marker::MessageMarker, Id},
-};
 
 use crate::{
+    branding::Branding,
+    config::Config,
     db::*,
-    error::ApplicationError,
-    statuspage::{StatuspageAPI, StatuspageUpdates, Update},
+    discord::Discord,
+    dry_run::DryRunSink,
+    http::HttpState,
+    metrics::Metrics,
+    mqtt::MqttPublisher,
+    reconciler::Reconciler,
+    replay::{Recorder, Replay},
+    scheduler::{sort_by_priority, Scheduler},
+    sinks::{
+        Delivered,
+        DiscordBotSink,
+        DiscordWebhookSink,
+        EmailSink,
+        GotifySink,
+        MatrixSink,
+        Notification,
+        NotificationKind,
+        NtfySink,
+        Sinks,
+        SlackBotSink,
+        SlackWebhookSink,
+        TeamsSink,
+        TelegramSink,
+        WebhookSink,
+    },
+    statuspage::{
+        IncidentSource,
+        IncidentStatus,
+        StatuspageAPI,
+        StatuspageUpdates,
+        Update,
+    },
 };
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing_subscriber::fmt::init();
 
-    let pg_url =
-        env::var("DATABASE_URL").expect("Missing `DATABASE_URL` in env");
-    let discord_token =
-        env::var("DISCORD_TOKEN").expect("Missing `DISCORD_TOKEN` in env");
-
-    let pg_pool = PgPoolOptions::new().connect(pg_url.as_str()).await?;
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            tracing::error!("Invalid configuration: {}", err);
+            std::process::exit(1);
+        },
+    };
+
+    let pg_pool = PgPoolOptions::new()
+        .connect(config.database_url.as_str())
+        .await?;
     info!("Connected to PostgreSQL");
 
-    let discord_rest_client = DiscordRestClient::new(discord_token);
+    let discord_rest_client =
+        DiscordRestClient::new(config.discord_token.clone());
     let current_user =
         discord_rest_client.current_user().await?.model().await?;
 
@@ -47,53 +107,269 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         "Using Discord token for",
     );
 
-    let db = Database::new(pg_pool);
+    let dry_run = if config.dry_run.enabled {
+        warn!("Dry run enabled, messages will not be sent");
+        Some(DryRunSink::new(config.dry_run.output.as_deref())?)
+    } else {
+        None
+    };
 
-    let statuspage_api = StatuspageAPI::new();
-    let (mut su, poll) = StatuspageUpdates::new(statuspage_api);
-    let (stop_tx, stop_poll_rx) = broadcast::channel(1);
+    let discord = Arc::new(Discord::new(discord_rest_client).await?);
 
-    let mut stop_handler_rx = stop_tx.subscribe();
+    let mut sinks = Sinks::new(dry_run);
+    sinks.register(SinkKind::DiscordBot, DiscordBotSink::new(discord.clone()));
+    sinks.register(
+        SinkKind::DiscordWebhook,
+        DiscordWebhookSink::new(discord.clone()),
+    );
+    sinks.register(SinkKind::SlackWebhook, SlackWebhookSink::default());
+    sinks.register(SinkKind::Webhook, WebhookSink::default());
+    sinks.register(SinkKind::Teams, TeamsSink::default());
+    sinks.register(SinkKind::Ntfy, NtfySink::default());
+    sinks.register(SinkKind::Gotify, GotifySink::default());
+    if let (Some(url), Some(token)) =
+        (&config.matrix.homeserver_url, &config.matrix.access_token)
+    {
+        sinks.register(
+            SinkKind::Matrix,
+            MatrixSink::new(url.parse()?, token.clone()),
+        );
+    }
+    if let Some(url) = &config.smtp.url {
+        sinks.register(
+            SinkKind::Email,
+            EmailSink::new(url, config.smtp.from.parse()?)?,
+        );
+    }
+    if let Some(token) = &config.slack.bot_token {
+        sinks.register(
+            SinkKind::SlackBot,
+            SlackBotSink::new(token.clone(), &config.slack.api_url),
+        );
+    }
+    if let Some(token) = &config.telegram.bot_token {
+        sinks.register(
+            SinkKind::Telegram,
+            TelegramSink::new(token.clone(), &config.telegram.api_url),
+        );
+    }
+    let sinks = Arc::new(sinks);
+
+    let scheduler = Arc::new(Scheduler::new(config.delivery_concurrency));
+    let db = Arc::new(Database::new(pg_pool, config.dry_run.enabled));
+    let branding = Arc::new(config.branding.clone());
+    let metrics = Arc::new(Metrics::new());
+
+    let (stop_http_tx, stop_http_rx) = oneshot::channel::<()>();
+    let http_handle = config.features.http.then(|| {
+        tokio::spawn(http::serve(
+            config.http_addr,
+            HttpState {
+                db: db.clone(),
+                discord: discord.clone(),
+                metrics: metrics.clone(),
+                poll_interval: config.poll_interval(),
+                branding: branding.clone(),
+                statuspage_url: config.statuspage_url.clone(),
+            },
+            async {
+                stop_http_rx.await.ok();
+            },
+        ))
+    });
+
+    let statuspage_api = StatuspageAPI::new(config.statuspage_url.clone());
+    let source: Box<dyn IncidentSource> =
+        match (&config.replay_path, &config.record_path) {
+            (Some(path), _) => {
+                info!(path, "Replaying recorded incidents");
+                Box::new(Replay::load(path)?)
+            },
+            (None, Some(path)) => {
+                info!(path, "Recording incidents");
+                Box::new(Recorder::new(Box::new(statuspage_api.clone()), path)?)
+            },
+            (None, None) => Box::new(statuspage_api.clone()),
+        };
+
+    // the reconciler checks the live status page, which a replay shouldn't
+    // be mixed with
+    let reconcile = config.features.reconciler && config.replay_path.is_none();
+    let reconciler = reconcile.then(|| {
+        Reconciler::new(
+            statuspage_api.clone(),
+            db.clone(),
+            sinks.clone(),
+            scheduler.clone(),
+            branding.clone(),
+            metrics.clone(),
+            config.reconcile_interval(),
+        )
+    });
+    let (mut su, poll) = StatuspageUpdates::new(
+        source,
+        config.update_queue_size,
+        config.poll_interval(),
+        metrics.clone(),
+    );
+    let (stop_tx, stop_poll_rx) = broadcast::channel(1);
+    let stop_reconciler_rx = stop_tx.subscribe();
+
+    let (mqtt, mqtt_handles) = match &config.mqtt.url {
+        Some(_) if config.dry_run.enabled => {
+            warn!("Dry run enabled, not publishing to MQTT");
+            (None, None)
+        },
+        Some(url) => {
+            let (publisher, connection) = MqttPublisher::new(
+                MqttOptions::parse_url(url)?,
+                config.mqtt.topic_prefix.clone(),
+            );
+            let publisher = Arc::new(publisher);
+            let connection_handle = tokio::spawn(connection.start());
+
+            // statuses come from the live status page, so a replay only
+            // publishes events
+            let status_publisher = publisher.clone();
+            let statuspage_api = statuspage_api.clone();
+            let interval = config.poll_interval();
+            let stop_mqtt_rx = stop_tx.subscribe();
+            let replaying = config.replay_path.is_some();
+            let status_handle = tokio::spawn(async move {
+                if !replaying {
+                    status_publisher
+                        .start(statuspage_api, interval, stop_mqtt_rx)
+                        .await;
+                }
+            });
+
+            (Some(publisher), Some((connection_handle, status_handle)))
+        },
+        None => (None, None),
+    };
+
+    // the stream ends once the poller stops and everything queued before
+    // that was delivered
+    let listener_sinks = sinks.clone();
+    let listener_scheduler = scheduler.clone();
+    let listener_metrics = metrics.clone();
+    let listener_mqtt = mqtt.clone();
     let listener_handle = tokio::spawn(async move {
-        loop {
-            tokio::select! {
-                Some(updates) = su.next() => {
-                    handle_updates(updates, &db, &discord_rest_client).await;
-                },
-                _ = stop_handler_rx.recv() => {
-                    info!("recvd stop signal");
-                    break;
-                },
+        while let Some(updates) = su.next().await {
+            handle_updates(
+                updates,
+                &db,
+                &listener_sinks,
+                &listener_scheduler,
+                &branding,
+                &listener_metrics,
+                listener_mqtt.as_deref(),
+            )
+            .await;
+
+            if listener_scheduler.is_closed() {
+                break;
             }
         }
-    });
-
-    let ctrl_c_handle = tokio::spawn(async move {
-        tokio::signal::ctrl_c().await.ok();
-        info!("Sending stop signal");
 
-        stop_tx.send(()).unwrap();
+        info!("Finished delivering updates");
     });
 
+    let statuspage_url = config.statuspage_url.clone();
+    let (poll_done_tx, poll_done_rx) = oneshot::channel::<()>();
     let poll_handle = tokio::spawn(async move {
-        info!(
-            source = env::var("STATUSPAGE_URL").unwrap(),
-            "Begin polling for updates"
-        );
+        info!(source = statuspage_url, "Begin polling for updates");
         poll.start(stop_poll_rx).await;
+        poll_done_tx.send(()).ok();
+    });
+
+    let reconciler_handle = tokio::spawn(async move {
+        if let Some(reconciler) = reconciler {
+            reconciler.start(stop_reconciler_rx).await;
+        }
     });
 
-    let (_, _, _) = futures::join!(listener_handle, poll_handle, ctrl_c_handle);
+    // the poller only stops on its own once a replay is finished, and
+    // there's nothing left to do after that
+    tokio::select! {
+        _ = shutdown_signal() => {},
+        _ = poll_done_rx => info!("Polling stopped"),
+    }
+    info!("Sending stop signal");
+    stop_tx.send(()).ok();
+
+    // give in-flight deliveries a chance to finish. after the deadline,
+    // anything still queued fails right away so the handlers can save what
+    // was already sent and release the rest
+    let mut deliveries =
+        futures::future::join(listener_handle, reconciler_handle);
+    if time::timeout(config.shutdown_timeout(), &mut deliveries)
+        .await
+        .is_err()
+    {
+        warn!("Deliveries did not finish in time, cancelling the rest");
+        scheduler.close();
+        sinks.close();
+        let (_, _) = deliveries.await;
+    }
+
+    poll_handle.await.ok();
+
+    // the last events were published once the listener finished
+    if let (Some(mqtt), Some((connection_handle, status_handle))) =
+        (mqtt, mqtt_handles)
+    {
+        status_handle.await.ok();
+        mqtt.disconnect();
+        if time::timeout(config.shutdown_timeout(), connection_handle)
+            .await
+            .is_err()
+        {
+            warn!("Could not disconnect from MQTT broker in time");
+        }
+    }
+
+    // health checks keep working until everything else stopped
+    stop_http_tx.send(()).ok();
+    if let Some(http_handle) = http_handle {
+        http_handle.await.ok();
+    }
+    info!("Shut down");
 
     Ok(())
 }
 
+/// Waits for Ctrl-C or SIGTERM
+async fn shutdown_signal() {
+    let mut sigterm =
+        signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {},
+        _ = sigterm.recv() => {},
+    }
+}
+
 async fn handle_updates(
-    updates: Vec<Update>,
+    mut updates: Vec<Update>,
     db: &Database,
-    discord_rest_client: &DiscordRestClient,
+    sinks: &Sinks,
+    scheduler: &Scheduler,
+    branding: &Branding,
+    metrics: &Metrics,
+    mqtt: Option<&MqttPublisher>,
 ) {
+    sort_by_priority(&mut updates);
+
+    if let Some(mqtt) = mqtt {
+        mqtt.publish_updates(&updates);
+    }
+
     for update in &updates {
+        if let Err(err) = db.save_incident(update.incident()).await {
+            tracing::error!("Failed to save incident: {:#?}", err);
+        }
+
         match update {
             Update::Created(i) => {
                 let subs =
@@ -113,29 +389,68 @@ async fn handle_updates(
                     continue;
                 }
 
-                let futs = subs.into_iter().map(|s| async {
-                    let embed = match s.mode {
-                        SubscriptionMode::Post => {
-                            make_post_embed(i, &i.incident_updates[0])
-                        },
-                        SubscriptionMode::Edit => make_edit_embed(i),
-                    };
+                let claimed = match db
+                    .claim_sent_updates(
+                        &i.id,
+                        &i.incident_updates[0].id,
+                        subs.iter()
+                            .map(|s| (s.subscription_id, s.mode))
+                            .collect(),
+                    )
+                    .await
+                {
+                    Ok(claimed) => claimed,
+                    Err(err) => {
+                        tracing::error!("Failed to claim updates: {:#?}", err);
+                        continue;
+                    },
+                };
+                let subs: Vec<_> = subs
+                    .into_iter()
+                    .filter(|s| claimed.contains(&s.subscription_id))
+                    .collect();
 
+                let futs = subs.into_iter().map(|s| {
+                    let destination = s.destination();
+                    let route = sinks.route(&destination);
                     (
-                        create_message(
-                            &discord_rest_client,
-                            s.channel_id,
-                            s.webhook_id,
-                            &s.webhook_token,
-                            &s.role_pings,
-                            embed,
-                        )
-                        .await,
-                        s,
+                        route,
+                        metrics.track(
+                            "created",
+                            i.incident_updates[0].created_at,
+                            async move {
+                                let kind = match s.mode {
+                                    SubscriptionMode::Post => {
+                                        NotificationKind::Post(
+                                            &i.incident_updates[0],
+                                        )
+                                    },
+                                    SubscriptionMode::Edit => {
+                                        NotificationKind::Edit
+                                    },
+                                };
+                                let notification = Notification {
+                                    incident: i,
+                                    kind,
+                                    update: Some(update),
+                                    branding: branding
+                                        .with_overrides(s.branding.as_ref()),
+                                    format: s.format,
+                                    role_pings: s.role_pings.clone(),
+                                };
+
+                                (
+                                    sinks
+                                        .create(&destination, &notification)
+                                        .await,
+                                    s,
+                                )
+                            },
+                        ),
                     )
                 });
 
-                let j = join_all(futs).await;
+                let j = scheduler.run(futs).await;
                 let total = j.len();
 
                 // https://doc.rust-lang.org/rust-by-example/error/iter_result.html
@@ -149,16 +464,26 @@ async fn handle_updates(
                     "Sent incident created messages",
                 );
 
+                let failed: Vec<_> =
+                    fail.iter().map(|(_, sub)| sub.subscription_id).collect();
+                if let Err(err) = db
+                    .release_sent_updates(&i.incident_updates[0].id, &failed)
+                    .await
+                {
+                    tracing::error!("Failed to release updates: {:#?}", err);
+                }
+
                 let success: Vec<_> = success
                     .into_iter()
-                    .map(|(msg_id, sub)| {
-                        let msg_id = msg_id.unwrap();
+                    .map(|(delivered, sub)| {
+                        let delivered = delivered.unwrap();
                         CreateSentUpdate {
                             mode: sub.mode,
-                            message_id: msg_id.get() as i64,
+                            message_ref: delivered.message_ref,
                             incident_id: &i.id,
                             incident_update_id: &i.incident_updates[0].id,
                             subscription_id: sub.subscription_id,
+                            content_hash: delivered.content_hash,
                         }
                     })
                     .collect();
@@ -190,57 +515,103 @@ async fn handle_updates(
                     continue;
                 }
 
-                let futs = subs.into_iter().map(|s| async {
-                    match (s.mode, s.message_id) {
-                        (SubscriptionMode::Edit, Some(msg_id)) => {
-                            let embed = make_edit_embed(i);
-                            (
-                                update_message(
-                                    &discord_rest_client,
-                                    s.channel_id,
-                                    s.webhook_id,
-                                    &s.webhook_token,
-                                    msg_id,
-                                    embed,
-                                )
-                                .await,
-                                s,
-                            )
-                        },
-                        (SubscriptionMode::Edit, None) => {
-                            let embed = make_edit_embed(i);
-                            (
-                                create_message(
-                                    &discord_rest_client,
-                                    s.channel_id,
-                                    s.webhook_id,
-                                    &s.webhook_token,
-                                    &s.role_pings,
-                                    embed,
-                                )
-                                .await,
-                                s,
-                            )
-                        },
-                        (SubscriptionMode::Post, _) => {
-                            let embed = make_post_embed(i, u);
-                            (
-                                create_message(
-                                    &discord_rest_client,
-                                    s.channel_id,
-                                    s.webhook_id,
-                                    &s.webhook_token,
-                                    &s.role_pings,
-                                    embed,
-                                )
-                                .await,
-                                s,
-                            )
-                        },
-                    }
+                let claimed = match db
+                    .claim_sent_updates(
+                        &i.id,
+                        &u.id,
+                        subs.iter()
+                            .map(|s| (s.subscription_id, s.mode))
+                            .collect(),
+                    )
+                    .await
+                {
+                    Ok(claimed) => claimed,
+                    Err(err) => {
+                        tracing::error!("Failed to claim updates: {:#?}", err);
+                        continue;
+                    },
+                };
+                let subs: Vec<_> = subs
+                    .into_iter()
+                    .filter(|s| claimed.contains(&s.subscription_id))
+                    .collect();
+
+                let futs = subs.into_iter().map(|s| {
+                    let destination = s.destination();
+                    let route = sinks.route(&destination);
+                    (
+                        route,
+                        metrics.track(
+                            "update_created",
+                            u.created_at,
+                            async move {
+                                let kind = match s.mode {
+                                    SubscriptionMode::Post => {
+                                        NotificationKind::Post(u)
+                                    },
+                                    SubscriptionMode::Edit => {
+                                        NotificationKind::Edit
+                                    },
+                                };
+                                let notification = Notification {
+                                    incident: i,
+                                    kind,
+                                    update: Some(update),
+                                    branding: branding
+                                        .with_overrides(s.branding.as_ref()),
+                                    format: s.format,
+                                    role_pings: s.role_pings.clone(),
+                                };
+
+                                let res = match (s.mode, &s.message_ref) {
+                                    (
+                                        SubscriptionMode::Edit,
+                                        Some(message_ref),
+                                    ) if sinks.can_edit(&destination) => {
+                                        let content_hash = sinks.content_hash(
+                                            &destination,
+                                            &notification,
+                                        );
+
+                                        // coalesced updates to the same incident all
+                                        // render the latest state, so only the first
+                                        // one needs to edit the message
+                                        match content_hash {
+                                            Some(content_hash)
+                                                if s.content_hash.as_ref()
+                                                    == Some(&content_hash) =>
+                                            {
+                                                Ok(Delivered {
+                                                    message_ref: message_ref
+                                                        .clone(),
+                                                    content_hash,
+                                                })
+                                            },
+                                            _ => {
+                                                sinks
+                                                    .edit_or_create(
+                                                        &destination,
+                                                        message_ref,
+                                                        &notification,
+                                                    )
+                                                    .await
+                                            },
+                                        }
+                                    },
+                                    _ => {
+                                        sinks
+                                            .create(&destination, &notification)
+                                            .await
+                                    },
+                                };
+
+                                (res, s)
+                            },
+                        ),
+                    )
                 });
 
-                let j = join_all(futs).await;
+                let j = scheduler.run(futs).await;
                 let total_len = j.len();
 
                 let (success, fail): (Vec<_>, Vec<_>) =
@@ -252,17 +623,52 @@ async fn handle_updates(
                     total = total_len,
                     "Sent incident update created messages",
                 );
+
+                let failed: Vec<_> =
+                    fail.iter().map(|(_, sub)| sub.subscription_id).collect();
+                if let Err(err) = db.release_sent_updates(&u.id, &failed).await
+                {
+                    tracing::error!("Failed to release updates: {:#?}", err);
+                }
+
+                for (delivered, sub) in &success {
+                    let (Ok(delivered), Some(old_ref)) =
+                        (delivered, &sub.message_ref)
+                    else {
+                        continue;
+                    };
+
+                    if delivered.message_ref == *old_ref {
+                        continue;
+                    }
+
+                    if let Err(err) = db
+                        .replace_message_ref(
+                            sub.subscription_id,
+                            &i.id,
+                            old_ref,
+                            &delivered.message_ref,
+                        )
+                        .await
+                    {
+                        tracing::error!(
+                            "Failed to replace message ref: {:#?}",
+                            err
+                        );
+                    }
+                }
                 let success: Vec<_> = success
                     .into_iter()
-                    .map(|(msg_id, sub)| {
-                        let msg_id = msg_id.unwrap();
+                    .map(|(delivered, sub)| {
+                        let delivered = delivered.unwrap();
 
                         CreateSentUpdate {
                             mode: sub.mode,
-                            message_id: msg_id.get() as i64,
+                            message_ref: delivered.message_ref,
                             incident_id: &i.id,
                             incident_update_id: &u.id,
                             subscription_id: sub.subscription_id,
+                            content_hash: delivered.content_hash,
                         }
                     })
                     .collect();
@@ -295,31 +701,105 @@ async fn handle_updates(
                 }
 
                 let futs = subs.iter().map(|s| {
-                    let embed = match s.mode {
-                        SubscriptionMode::Post => make_post_embed(i, u_new),
-                        SubscriptionMode::Edit => make_edit_embed(i),
+                    // only the edit mode message can be sent again if it was
+                    // deleted, since it has the whole incident
+                    let recreate = s.mode == SubscriptionMode::Edit
+                        && u_new.status != IncidentStatus::Postmortem;
+                    let kind = match s.mode {
+                        _ if u_new.status == IncidentStatus::Postmortem => {
+                            NotificationKind::Postmortem(u_new)
+                        },
+                        SubscriptionMode::Post => NotificationKind::Post(u_new),
+                        SubscriptionMode::Edit => NotificationKind::Edit,
+                    };
+                    let notification = Notification {
+                        incident: i,
+                        kind,
+                        update: Some(update),
+                        branding: branding.with_overrides(s.branding.as_ref()),
+                        format: s.format,
+                        role_pings: s.role_pings.clone(),
                     };
 
-                    update_message(
-                        &discord_rest_client,
-                        s.channel_id,
-                        s.webhook_id,
-                        &s.webhook_token,
-                        s.message_id,
-                        embed,
+                    let destination = s.destination();
+                    let route = sinks.route(&destination);
+                    (
+                        route,
+                        metrics.track(
+                            "update_modified",
+                            u_new.updated_at,
+                            async move {
+                                let res = if recreate {
+                                    sinks
+                                        .edit_or_create(
+                                            &destination,
+                                            &s.message_ref,
+                                            &notification,
+                                        )
+                                        .await
+                                } else {
+                                    sinks
+                                        .edit(
+                                            &destination,
+                                            &s.message_ref,
+                                            &notification,
+                                        )
+                                        .await
+                                };
+
+                                (res, s)
+                            },
+                        ),
                     )
                 });
 
-                let j = join_all(futs).await;
+                let j = scheduler.run(futs).await;
 
                 let (successes, fails) = j.iter().fold((0, 0), |mut a, c| {
-                    match c {
+                    match c.0 {
                         Ok(_) => a.0 += 1,
                         Err(_) => a.1 += 1,
                     };
                     a
                 });
 
+                for (delivered, sub) in &j {
+                    let Ok(delivered) = delivered else {
+                        continue;
+                    };
+
+                    if delivered.message_ref != sub.message_ref {
+                        if let Err(err) = db
+                            .replace_message_ref(
+                                sub.subscription_id,
+                                &i.id,
+                                &sub.message_ref,
+                                &delivered.message_ref,
+                            )
+                            .await
+                        {
+                            tracing::error!(
+                                "Failed to replace message ref: {:#?}",
+                                err
+                            );
+                        }
+                    }
+
+                    if let Err(err) = db
+                        .set_content_hash(
+                            sub.subscription_id,
+                            &delivered.message_ref,
+                            &delivered.content_hash,
+                        )
+                        .await
+                    {
+                        tracing::error!(
+                            "Failed to save content hash: {:#?}",
+                            err
+                        );
+                    }
+                }
+
                 info!(
                     success = successes,
                     fail = fails,
@@ -327,106 +807,119 @@ async fn handle_updates(
                     "Edited incident update messages"
                 );
             },
-        }
-    }
-}
 
-async fn create_message(
-    rest_client: &DiscordRestClient,
-    channel_id: i64,
-    webhook_id: Option<i64>,
-    webhook_token: &Option<String>,
-    role_pings: &Vec<i64>,
-    embed: Embed,
-) -> Result<Id<MessageMarker>, ApplicationError> {
-    let created_msg =
-        if let (Some(id), Some(token)) = (webhook_id, webhook_token) {
-            rest_client
-                .execute_webhook(Id::new(id as u64), &token)
-                .content(
-                    &role_pings
-                        .iter()
-                        .map(|r| format!("<@&{r}>"))
-                        .collect::<Vec<_>>()
-                        .join(" "),
-                )
-                .unwrap()
-                .embeds(&[embed])
-                .unwrap()
-                .wait()
-                .await
-                .map_err(|e| ApplicationError::MessageSendError {
-                    channel_id: channel_id as u64,
-                    webhook_id: Some(id as u64),
-                    error: e,
-                })
-        } else {
-            rest_client
-                .create_message(Id::new(channel_id as u64))
-                .content(
-                    &role_pings
-                        .iter()
-                        .map(|r| format!("<@&{r}>"))
-                        .collect::<Vec<_>>()
-                        .join(" "),
-                )
-                .unwrap()
-                .embeds(&[embed])
-                .unwrap()
-                .await
-                .map_err(|e| ApplicationError::MessageSendError {
-                    channel_id: channel_id as u64,
-                    webhook_id: None,
-                    error: e,
-                })
-        }?
-        .model()
-        .await
-        .map_err(ApplicationError::from)?;
+            Update::PostmortemPublished(i, u) => {
+                let subs =
+                    match db.get_postmortem_subscriptions(&i.id, &u.id).await {
+                        Ok(subs) => subs,
+                        Err(err) => {
+                            tracing::error!(
+                                "Failed to get subscriptions: {:#?}",
+                                err
+                            );
+                            continue;
+                        },
+                    };
 
-    Ok(created_msg.id)
-}
+                if subs.is_empty() {
+                    continue;
+                }
 
-async fn update_message(
-    rest_client: &DiscordRestClient,
-    channel_id: i64,
-    webhook_id: Option<i64>,
-    webhook_token: &Option<String>,
-    message_id: i64,
-    embed: Embed,
-) -> Result<Id<MessageMarker>, ApplicationError> {
-    if let (Some(id), Some(token)) = (webhook_id, webhook_token) {
-        rest_client
-            .update_webhook_message(
-                Id::new(id as u64),
-                &token,
-                Id::new(message_id as u64),
-            )
-            .embeds(Some(&[embed]))
-            .unwrap()
-            .await
-            .map_err(|e| ApplicationError::MessageEditError {
-                channel_id: channel_id as u64,
-                webhook_id: Some(id as u64),
-                message_id: message_id as u64,
-                error: e,
-            })?;
-    } else {
-        rest_client
-            .update_message(
-                Id::new(channel_id as u64),
-                Id::new(message_id as u64),
-            )
-            .embeds(Some(&[embed]))
-            .unwrap()
-            .await
-            .map_err(|e| ApplicationError::MessageEditError {
-                channel_id: channel_id as u64,
-                webhook_id: None,
-                message_id: message_id as u64,
-                error: e,
-            })?;
-    }
+                let claimed = match db
+                    .claim_sent_updates(
+                        &i.id,
+                        &u.id,
+                        subs.iter()
+                            .map(|s| {
+                                (s.subscription_id, SubscriptionMode::Post)
+                            })
+                            .collect(),
+                    )
+                    .await
+                {
+                    Ok(claimed) => claimed,
+                    Err(err) => {
+                        tracing::error!("Failed to claim updates: {:#?}", err);
+                        continue;
+                    },
+                };
+                let subs: Vec<_> = subs
+                    .into_iter()
+                    .filter(|s| claimed.contains(&s.subscription_id))
+                    .collect();
+
+                // postmortems are always posted as a new message, and don't
+                // ping since they aren't time sensitive (see `MessageBody`)
+                let futs = subs.into_iter().map(|s| {
+                    let destination = s.destination();
+                    let route = sinks.route(&destination);
+                    (
+                        route,
+                        metrics.track(
+                            "postmortem_published",
+                            u.created_at,
+                            async move {
+                                let notification = Notification {
+                                    incident: i,
+                                    kind: NotificationKind::Postmortem(u),
+                                    update: Some(update),
+                                    branding: branding
+                                        .with_overrides(s.branding.as_ref()),
+                                    format: s.format,
+                                    role_pings: vec![],
+                                };
+
+                                (
+                                    sinks
+                                        .create(&destination, &notification)
+                                        .await,
+                                    s,
+                                )
+                            },
+                        ),
+                    )
+                });
+
+                let j = scheduler.run(futs).await;
+                let total = j.len();
+
+                let (success, fail): (Vec<_>, Vec<_>) =
+                    j.into_iter().partition(|f| f.0.is_ok());
+
+                info!(
+                    success = success.len(),
+                    fail = fail.len(),
+                    total = total,
+                    "Sent postmortem messages",
+                );
+
+                let failed: Vec<_> =
+                    fail.iter().map(|(_, sub)| sub.subscription_id).collect();
+                if let Err(err) = db.release_sent_updates(&u.id, &failed).await
+                {
+                    tracing::error!("Failed to release updates: {:#?}", err);
+                }
+
+                let success: Vec<_> = success
+                    .into_iter()
+                    .map(|(delivered, sub)| {
+                        let delivered = delivered.unwrap();
+                        CreateSentUpdate {
+                            mode: SubscriptionMode::Post,
+                            message_ref: delivered.message_ref,
+                            incident_id: &i.id,
+                            incident_update_id: &u.id,
+                            subscription_id: sub.subscription_id,
+                            content_hash: delivered.content_hash,
+                        }
+                    })
+                    .collect();
 
-    Ok(Id::new(message_id as u64))
+                if let Err(err) = db.create_many_sent_updates(success).await {
+                    tracing::error!("Failed to save update: {:#?}", err);
+                    continue;
+                }
+            },
+        }
+    }
 }
@@ -0,0 +1,141 @@
+use serde::Serialize;
+
+use crate::{
+    branding::Branding,
+    embeds::POSTMORTEM_EXCERPT_MAX_LEN,
+    statuspage::{Incident, IncidentStatus, IncidentUpdate},
+    text::{
+        get_update_heading,
+        make_edit_text,
+        make_post_text,
+        make_postmortem_text,
+        make_update_html,
+        TextStyle,
+    },
+    util::{
+        escape_html,
+        fit_timeline,
+        get_embed_color,
+        get_excerpt,
+        get_formatted_utc_timestamp,
+        get_timeline_updates,
+    },
+};
+
+/// Events can be up to 64KiB. Edits have the plain text and HTML twice, as
+/// the fallback and the new content, so each is kept under a fifth of that
+/// to leave room for escaping
+const BODY_MAX_LEN: usize = 12000;
+const UPDATES_MAX_LEN: usize = 12000;
+
+/// Keeps one long update from pushing every other one out of the timeline.
+/// Escaping can make it longer, but it's always shown
+const UPDATE_BODY_MAX_LEN: usize = 4000;
+
+/// The content of an `m.room.message` event
+/// https://spec.matrix.org/v1.8/client-server-api/#mroommessage
+#[derive(Clone, Debug, Serialize)]
+pub struct MatrixMessage {
+    pub msgtype: &'static str,
+
+    /// Shown by clients that can't render HTML
+    pub body: String,
+    pub format: &'static str,
+    pub formatted_body: String,
+}
+
+impl MatrixMessage {
+    pub fn post(
+        incident: &Incident,
+        update: &IncidentUpdate,
+        branding: &Branding,
+    ) -> Self {
+        Self::build(
+            make_post_text(
+                incident,
+                update,
+                branding,
+                TextStyle::Plain,
+                BODY_MAX_LEN,
+            ),
+            incident,
+            branding,
+            &update.status,
+            vec![make_update_html(update, branding, UPDATE_BODY_MAX_LEN)],
+        )
+    }
+
+    pub fn edit(incident: &Incident, branding: &Branding) -> Self {
+        let updates = fit_timeline(
+            get_timeline_updates(incident).into_iter().map(|upd| {
+                make_update_html(upd, branding, UPDATE_BODY_MAX_LEN)
+            }),
+            UPDATES_MAX_LEN,
+        );
+
+        Self::build(
+            make_edit_text(incident, branding, TextStyle::Plain, BODY_MAX_LEN),
+            incident,
+            branding,
+            &incident.status,
+            updates,
+        )
+    }
+
+    pub fn postmortem(
+        incident: &Incident,
+        update: &IncidentUpdate,
+        branding: &Branding,
+    ) -> Self {
+        let excerpt = format!(
+            "{}<br>{}<br><br><a href=\"{}\">Read the full postmortem</a>",
+            get_update_heading(update, branding, TextStyle::Html),
+            escape_html(&get_excerpt(&update.body, POSTMORTEM_EXCERPT_MAX_LEN))
+                .replace('\n', "<br>"),
+            escape_html(&incident.shortlink),
+        );
+
+        Self::build(
+            make_postmortem_text(
+                incident,
+                update,
+                branding,
+                TextStyle::Plain,
+                BODY_MAX_LEN,
+            ),
+            incident,
+            branding,
+            &update.status,
+            vec![format!("<p>{}</p>", excerpt)],
+        )
+    }
+
+    /// Clients drop `style` attributes, so the embed colour is a coloured
+    /// square before the name
+    fn build(
+        body: String,
+        incident: &Incident,
+        branding: &Branding,
+        status: &IncidentStatus,
+        updates: Vec<String>,
+    ) -> Self {
+        let header = format!(
+            "<h4><font data-mx-color=\"#{:06X}\">■</font> <a href=\"{}\">{}</a></h4>",
+            get_embed_color(branding, incident, status),
+            escape_html(&incident.shortlink),
+            escape_html(&incident.name),
+        );
+        let footer = format!(
+            "<p><sub>{} · Started {}</sub></p>",
+            escape_html(&branding.author.name),
+            get_formatted_utc_timestamp(&incident.start_time()),
+        );
+
+        Self {
+            msgtype: "m.notice",
+            body,
+            format: "org.matrix.custom.html",
+            formatted_body: header + &updates.concat() + &footer,
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use serde::Serialize;
+use twilight_model::channel::message::{Component, Embed};
+
+use crate::{
+    branding::Branding,
+    components::make_link_buttons,
+    db::MessageFormat,
+    embeds::{make_edit_embed, make_post_embed, make_postmortem_embed},
+    sinks::hash_json,
+    statuspage::{Incident, IncidentUpdate},
+    text::{
+        make_edit_text,
+        make_post_text,
+        make_postmortem_text,
+        TextStyle,
+        CONTENT_MAX_LEN,
+    },
+};
+
+/// The content and embeds of a message, rendered in a subscription's
+/// preferred format
+#[derive(Clone, Debug, Serialize)]
+pub struct MessageBody {
+    pub content: String,
+    pub embeds: Vec<Embed>,
+    pub components: Vec<Component>,
+}
+
+impl MessageBody {
+    pub fn post(
+        incident: &Incident,
+        update: &IncidentUpdate,
+        branding: &Branding,
+        format: MessageFormat,
+        role_pings: &[i64],
+    ) -> Self {
+        Self::build(
+            format,
+            role_pings,
+            make_link_buttons(incident, branding),
+            || make_post_embed(incident, update, branding),
+            |len| {
+                make_post_text(
+                    incident,
+                    update,
+                    branding,
+                    TextStyle::Discord,
+                    len,
+                )
+            },
+        )
+    }
+
+    pub fn edit(
+        incident: &Incident,
+        branding: &Branding,
+        format: MessageFormat,
+        role_pings: &[i64],
+    ) -> Self {
+        Self::build(
+            format,
+            role_pings,
+            make_link_buttons(incident, branding),
+            || make_edit_embed(incident, branding),
+            |len| make_edit_text(incident, branding, TextStyle::Discord, len),
+        )
+    }
+
+    pub fn postmortem(
+        incident: &Incident,
+        update: &IncidentUpdate,
+        branding: &Branding,
+        format: MessageFormat,
+    ) -> Self {
+        Self::build(
+            format,
+            &[],
+            make_link_buttons(incident, branding),
+            || make_postmortem_embed(incident, update, branding),
+            |len| {
+                make_postmortem_text(
+                    incident,
+                    update,
+                    branding,
+                    TextStyle::Discord,
+                    len,
+                )
+            },
+        )
+    }
+
+    /// A hash of everything that gets sent, used to tell whether a message
+    /// on Discord is out of date
+    pub fn content_hash(&self) -> String {
+        hash_json(&(&self.content, &self.embeds, &self.components))
+    }
+
+    fn build(
+        format: MessageFormat,
+        role_pings: &[i64],
+        components: Vec<Component>,
+        embed: impl FnOnce() -> Embed,
+        text: impl FnOnce(usize) -> String,
+    ) -> Self {
+        let pings = role_pings
+            .iter()
+            .map(|r| format!("<@&{r}>"))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let embeds = match format {
+            MessageFormat::Text => vec![],
+            MessageFormat::Embed | MessageFormat::Both => vec![embed()],
+        };
+
+        let content = match format {
+            MessageFormat::Embed => pings,
+            MessageFormat::Text | MessageFormat::Both if pings.is_empty() => {
+                text(CONTENT_MAX_LEN)
+            },
+            MessageFormat::Text | MessageFormat::Both => {
+                let len = CONTENT_MAX_LEN.saturating_sub(pings.len() + 1);
+                pings + "\n" + &text(len)
+            },
+        };
+
+        Self {
+            content,
+            embeds,
+            components,
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::{
+    future::Future,
+    sync::atomic::{AtomicI64, Ordering},
+};
+
+use chrono::{DateTime, TimeZone, Utc};
+use prometheus::{
+    Encoder,
+    HistogramOpts,
+    HistogramVec,
+    IntCounter,
+    IntCounterVec,
+    Opts,
+    Registry,
+    TextEncoder,
+};
+
+use crate::{error::ApplicationError, statuspage::Update};
+
+/// Buckets for the time from an incident update being posted to it showing
+/// up in Discord, in seconds
+const LATENCY_BUCKETS: &[f64] =
+    &[1.0, 2.5, 5.0, 10.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0];
+
+/// Prometheus metrics, plus the state the readiness check needs
+pub struct Metrics {
+    registry: Registry,
+
+    polls: IntCounter,
+    poll_errors: IntCounter,
+    updates: IntCounterVec,
+    deliveries: IntCounterVec,
+    delivery_latency: HistogramVec,
+
+    /// Unix timestamp (ms) of the last successful poll, 0 if there wasn't
+    /// one yet
+    last_poll: AtomicI64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let polls = IntCounter::new(
+            "statuspage_polls_total",
+            "Successful status page polls",
+        )
+        .unwrap();
+        let poll_errors = IntCounter::new(
+            "statuspage_poll_errors_total",
+            "Failed status page polls",
+        )
+        .unwrap();
+        let updates = IntCounterVec::new(
+            Opts::new("statuspage_updates_total", "Detected incident changes"),
+            &["kind"],
+        )
+        .unwrap();
+        let deliveries = IntCounterVec::new(
+            Opts::new("deliveries_total", "Messages sent or edited"),
+            &["kind", "outcome"],
+        )
+        .unwrap();
+        let delivery_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "delivery_latency_seconds",
+                "Time from an incident update to its delivery",
+            )
+            .buckets(LATENCY_BUCKETS.to_vec()),
+            &["kind"],
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(polls.clone())).unwrap();
+        registry.register(Box::new(poll_errors.clone())).unwrap();
+        registry.register(Box::new(updates.clone())).unwrap();
+        registry.register(Box::new(deliveries.clone())).unwrap();
+        registry
+            .register(Box::new(delivery_latency.clone()))
+            .unwrap();
+
+        Self {
+            registry,
+            polls,
+            poll_errors,
+            updates,
+            deliveries,
+            delivery_latency,
+            last_poll: AtomicI64::new(0),
+        }
+    }
+
+    pub fn poll_succeeded(&self, updates: &[Update]) {
+        self.polls.inc();
+        self.last_poll
+            .store(Utc::now().timestamp_millis(), Ordering::Relaxed);
+
+        for update in updates {
+            self.updates.with_label_values(&[update_kind(update)]).inc();
+        }
+    }
+
+    pub fn poll_failed(&self) {
+        self.poll_errors.inc();
+    }
+
+    /// When the status page was last polled successfully
+    pub fn last_poll(&self) -> Option<DateTime<Utc>> {
+        match self.last_poll.load(Ordering::Relaxed) {
+            0 => None,
+            ms => Utc.timestamp_millis_opt(ms).single(),
+        }
+    }
+
+    /// Wraps a delivery job to record its outcome and, if it succeeded, how
+    /// long after `since` it was delivered
+    pub async fn track<T, S>(
+        &self,
+        kind: &'static str,
+        since: DateTime<Utc>,
+        job: impl Future<Output = (Result<T, ApplicationError>, S)>,
+    ) -> (Result<T, ApplicationError>, S) {
+        let (res, s) = job.await;
+
+        let outcome = if res.is_ok() { "success" } else { "failure" };
+        self.deliveries.with_label_values(&[kind, outcome]).inc();
+
+        if res.is_ok() {
+            let latency = Utc::now().signed_duration_since(since);
+            self.delivery_latency
+                .with_label_values(&[kind])
+                .observe(latency.num_milliseconds().max(0) as f64 / 1000.0);
+        }
+
+        (res, s)
+    }
+
+    /// Renders every metric in the Prometheus text format
+    pub fn encode(&self) -> String {
+        let mut buf = vec![];
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .unwrap();
+
+        String::from_utf8(buf).unwrap()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn update_kind(update: &Update) -> &'static str {
+    match update {
+        Update::Created(_) => "created",
+        Update::UpdateCreated(..) => "update_created",
+        Update::UpdateModified(..) => "update_modified",
+        Update::PostmortemPublished(..) => "postmortem_published",
+    }
+}
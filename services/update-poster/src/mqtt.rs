@@ -0,0 +1,240 @@
+//! Publishes the status page to an MQTT broker for dashboards and other
+//! devices. Under the configured prefix:
+//!
+//! - `online` is `true` while the service is connected, and `false` once it
+//!   stops or loses its connection
+//! - `status` is the page's overall status
+//! - `components/<id>` is each component's status
+//! - `events` gets every incident event, in the same JSON as webhooks
+//!
+//! Everything but `events` is retained, so new subscribers get the current
+//! state right away.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rumqttc::{
+    AsyncClient,
+    ConnectionError,
+    Event,
+    EventLoop,
+    Incoming,
+    LastWill,
+    MqttOptions,
+    Outgoing,
+    QoS,
+};
+use serde_json::json;
+use tokio::{
+    sync::broadcast::Receiver,
+    time::{self, MissedTickBehavior},
+};
+use tracing::{info, warn};
+
+use crate::{
+    statuspage::{StatuspageAPI, Summary, Update},
+    webhook::WebhookEvent,
+};
+
+/// How many messages can wait to be sent. Publishing never waits for the
+/// broker, so anything past this is dropped while it's unreachable
+const QUEUE_SIZE: usize = 256;
+
+/// How long to wait before reconnecting after the connection is lost
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// The last payload of each retained topic. Only changes are published, and
+/// everything is published again after reconnecting in case the broker lost
+/// it
+type Retained = Arc<Mutex<HashMap<String, String>>>;
+
+pub struct MqttPublisher {
+    client: AsyncClient,
+    topic_prefix: String,
+    retained: Retained,
+}
+
+/// The connection to the broker, which has to be running for anything to be
+/// sent
+pub struct MqttConnection {
+    eventloop: EventLoop,
+    client: AsyncClient,
+    retained: Retained,
+}
+
+impl MqttPublisher {
+    pub fn new(
+        mut options: MqttOptions,
+        topic_prefix: String,
+    ) -> (Self, MqttConnection) {
+        let online = format!("{}/online", topic_prefix);
+        options.set_last_will(LastWill::new(
+            &online,
+            "false",
+            QoS::AtLeastOnce,
+            true,
+        ));
+
+        let (client, eventloop) = AsyncClient::new(options, QUEUE_SIZE);
+        let retained = Retained::default();
+        retained.lock().unwrap().insert(online, "true".to_string());
+
+        (
+            Self {
+                client: client.clone(),
+                topic_prefix,
+                retained: retained.clone(),
+            },
+            MqttConnection {
+                eventloop,
+                client,
+                retained,
+            },
+        )
+    }
+
+    /// Keeps the page's and components' statuses up to date until stopped
+    pub async fn start(
+        &self,
+        statuspage_api: StatuspageAPI,
+        interval: Duration,
+        mut stop: Receiver<()>,
+    ) {
+        let mut interval = time::interval(interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    match statuspage_api.get_summary().await {
+                        Ok(summary) => self.publish_summary(&summary),
+                        Err(err) => {
+                            warn!("Failed to get status page summary: {:#?}", err);
+                        },
+                    }
+                },
+                _ = stop.recv() => {
+                    info!("recvd stop signal");
+                    break;
+                }
+            };
+        }
+    }
+
+    /// Publishes an event for each update
+    pub fn publish_updates(&self, updates: &[Update]) {
+        for update in updates {
+            let event = WebhookEvent::new(update.incident(), Some(update));
+            let payload = match serde_json::to_string(&event) {
+                Ok(payload) => payload,
+                Err(err) => {
+                    tracing::error!("Failed to serialize event: {:#?}", err);
+                    continue;
+                },
+            };
+
+            self.publish(self.topic("events"), payload, false);
+        }
+    }
+
+    /// Publishes the statuses that changed since they were last published
+    pub fn publish_summary(&self, summary: &Summary) {
+        let status = json!({
+            "indicator": summary.status.indicator,
+            "description": summary.status.description,
+        });
+        let components = summary.components.iter().map(|c| {
+            (
+                self.topic(&format!("components/{}", c.id)),
+                json!({
+                    "id": c.id,
+                    "name": c.name,
+                    "status": c.status,
+                }),
+            )
+        });
+
+        for (topic, payload) in
+            std::iter::once((self.topic("status"), status)).chain(components)
+        {
+            let payload = payload.to_string();
+
+            let changed = self
+                .retained
+                .lock()
+                .unwrap()
+                .insert(topic.clone(), payload.clone())
+                .as_ref()
+                != Some(&payload);
+
+            if changed {
+                self.publish(topic, payload, true);
+            }
+        }
+    }
+
+    /// Marks the service as offline and closes the connection once
+    /// everything before it was sent
+    pub fn disconnect(&self) {
+        self.publish(self.topic("online"), "false".to_string(), true);
+
+        if let Err(err) = self.client.try_disconnect() {
+            warn!("Failed to disconnect from MQTT broker: {}", err);
+        }
+    }
+
+    fn topic(&self, name: &str) -> String {
+        format!("{}/{}", self.topic_prefix, name)
+    }
+
+    fn publish(&self, topic: String, payload: String, retain: bool) {
+        publish(&self.client, topic, payload, retain);
+    }
+}
+
+impl MqttConnection {
+    /// Sends and receives everything until disconnected, reconnecting
+    /// whenever the connection is lost
+    pub async fn start(mut self) {
+        loop {
+            match self.eventloop.poll().await {
+                Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+                    info!("Connected to MQTT broker");
+                    self.republish();
+                },
+                Ok(Event::Outgoing(Outgoing::Disconnect))
+                | Err(ConnectionError::RequestsDone) => break,
+                Ok(_) => {},
+                Err(err) => {
+                    warn!("MQTT connection failed: {}", err);
+                    time::sleep(RECONNECT_DELAY).await;
+                },
+            }
+        }
+
+        info!("Disconnected from MQTT broker");
+    }
+
+    /// Publishes every retained topic again, since the broker might not have
+    /// kept them
+    fn republish(&self) {
+        let retained = self.retained.lock().unwrap().clone();
+
+        for (topic, payload) in retained {
+            publish(&self.client, topic, payload, true);
+        }
+    }
+}
+
+/// Queues a message without waiting, since deliveries to other sinks
+/// shouldn't wait for the broker
+fn publish(client: &AsyncClient, topic: String, payload: String, retain: bool) {
+    if let Err(err) =
+        client.try_publish(&topic, QoS::AtLeastOnce, retain, payload)
+    {
+        warn!(topic, "Failed to publish MQTT message: {}", err);
+    }
+}
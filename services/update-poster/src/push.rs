@@ -0,0 +1,86 @@
+use serde::Serialize;
+
+use crate::{
+    branding::Branding,
+    embeds::POSTMORTEM_EXCERPT_MAX_LEN,
+    statuspage::{Incident, IncidentUpdate, StatusIndicator},
+    util::{
+        get_excerpt,
+        get_formatted_utc_timestamp,
+        get_timeline_updates,
+        get_unicode_status_emoji,
+        truncate_with_ellipsis,
+    },
+};
+
+/// ntfy turns longer messages into attachments
+const MESSAGE_MAX_LEN: usize = 4000;
+
+/// A phone notification. Each service has its own priority scale, so the
+/// incident's impact is kept for them to map
+#[derive(Clone, Debug, Serialize)]
+pub struct PushMessage {
+    pub title: String,
+    pub message: String,
+    pub impact: StatusIndicator,
+
+    /// Opened when the notification is tapped
+    pub click: String,
+}
+
+impl PushMessage {
+    pub fn post(
+        incident: &Incident,
+        update: &IncidentUpdate,
+        branding: &Branding,
+    ) -> Self {
+        Self::build(incident, update, branding, &update.body)
+    }
+
+    /// Notifications can't be changed, so only the latest update is sent
+    pub fn edit(incident: &Incident, branding: &Branding) -> Self {
+        match get_timeline_updates(incident).first() {
+            Some(update) => {
+                Self::build(incident, update, branding, &update.body)
+            },
+            None => Self {
+                title: incident.name.clone(),
+                message: incident.status.to_string(),
+                impact: incident.impact,
+                click: incident.shortlink.clone(),
+            },
+        }
+    }
+
+    pub fn postmortem(
+        incident: &Incident,
+        update: &IncidentUpdate,
+        branding: &Branding,
+    ) -> Self {
+        let excerpt = get_excerpt(&update.body, POSTMORTEM_EXCERPT_MAX_LEN);
+
+        Self::build(incident, update, branding, &excerpt)
+    }
+
+    fn build(
+        incident: &Incident,
+        update: &IncidentUpdate,
+        branding: &Branding,
+        body: &str,
+    ) -> Self {
+        let message = format!(
+            "{} {} · {}\n{}",
+            get_unicode_status_emoji(branding, &update.status),
+            update.status,
+            get_formatted_utc_timestamp(&update.display_time()),
+            body,
+        );
+
+        Self {
+            title: incident.name.clone(),
+            message: truncate_with_ellipsis(message, MESSAGE_MAX_LEN),
+            impact: incident.impact,
+            click: incident.shortlink.clone(),
+        }
+    }
+}
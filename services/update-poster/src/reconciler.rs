@@ -0,0 +1,284 @@
+use std::{sync::Arc, time::Duration};
+
+use chrono::Utc;
+use tokio::{
+    sync::broadcast::Receiver,
+    time::{self, MissedTickBehavior},
+};
+use tracing::{info, warn};
+
+use crate::{
+    branding::Branding,
+    db::Database,
+    metrics::Metrics,
+    scheduler::Scheduler,
+    sinks::{Notification, NotificationKind, Sinks},
+    statuspage::{Incident, IncidentStatus, StatuspageAPI},
+};
+
+/// How long after an incident is resolved its messages are still checked
+const RESOLVED_WINDOW_HOURS: i64 = 6;
+
+/// Periodically re-renders edit mode messages and repairs the ones that no
+/// longer match the incident, e.g. because an edit failed
+pub struct Reconciler {
+    statuspage_api: StatuspageAPI,
+    db: Arc<Database>,
+    sinks: Arc<Sinks>,
+    scheduler: Arc<Scheduler>,
+    branding: Arc<Branding>,
+    metrics: Arc<Metrics>,
+    interval: Duration,
+}
+
+impl Reconciler {
+    pub fn new(
+        statuspage_api: StatuspageAPI,
+        db: Arc<Database>,
+        sinks: Arc<Sinks>,
+        scheduler: Arc<Scheduler>,
+        branding: Arc<Branding>,
+        metrics: Arc<Metrics>,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            statuspage_api,
+            db,
+            sinks,
+            scheduler,
+            branding,
+            metrics,
+            interval,
+        }
+    }
+
+    pub async fn start(&self, mut stop: Receiver<()>) {
+        let mut interval = time::interval(self.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        // the first tick completes immediately, and the poller already sends
+        // everything that changed since startup
+        interval.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => self.reconcile().await,
+                _ = stop.recv() => {
+                    info!("recvd stop signal");
+                    break;
+                }
+            };
+        }
+    }
+
+    async fn reconcile(&self) {
+        let incidents = match self.statuspage_api.get_all_incidents().await {
+            Ok(incidents) => incidents,
+            Err(err) => {
+                warn!("Failed to get status page incidents: {:#?}", err);
+                return;
+            },
+        };
+
+        for incident in incidents.incidents.iter().filter(|i| is_recent(i)) {
+            self.reconcile_incident(incident).await;
+        }
+    }
+
+    async fn reconcile_incident(&self, incident: &Incident) {
+        let messages =
+            match self.db.get_incident_edit_messages(&incident.id).await {
+                Ok(messages) => messages,
+                Err(err) => {
+                    tracing::error!("Failed to get sent messages: {:#?}", err);
+                    return;
+                },
+            };
+
+        let sinks = &self.sinks;
+        let jobs: Vec<_> = messages
+            .into_iter()
+            .filter_map(|m| {
+                let destination = m.destination();
+                let notification = Notification {
+                    incident,
+                    kind: NotificationKind::Edit,
+                    update: None,
+                    branding: self.branding.with_overrides(m.branding.as_ref()),
+                    format: m.format,
+                    role_pings: m.role_pings.clone(),
+                };
+
+                if !sinks.needs_repair(
+                    &destination,
+                    &notification,
+                    m.content_hash.as_ref(),
+                ) {
+                    return None;
+                }
+
+                let route = sinks.route(&destination);
+                let job = async move {
+                    let res = sinks
+                        .edit_or_create(
+                            &destination,
+                            &m.message_ref,
+                            &notification,
+                        )
+                        .await;
+
+                    (res, m)
+                };
+
+                Some((
+                    route,
+                    self.metrics.track("reconcile", incident.updated_at, job),
+                ))
+            })
+            .collect();
+
+        if jobs.is_empty() {
+            return;
+        }
+
+        let j = self.scheduler.run(jobs).await;
+        let total = j.len();
+        let mut repaired = 0;
+
+        for (res, m) in j {
+            let delivered = match res {
+                Ok(delivered) => delivered,
+                Err(err) => {
+                    tracing::error!("Failed to repair message: {:#?}", err);
+                    continue;
+                },
+            };
+
+            repaired += 1;
+
+            if delivered.message_ref != m.message_ref {
+                if let Err(err) = self
+                    .db
+                    .replace_message_ref(
+                        m.subscription_id,
+                        &incident.id,
+                        &m.message_ref,
+                        &delivered.message_ref,
+                    )
+                    .await
+                {
+                    tracing::error!(
+                        "Failed to replace message ref: {:#?}",
+                        err
+                    );
+                }
+            }
+
+            if let Err(err) = self
+                .db
+                .set_content_hash(
+                    m.subscription_id,
+                    &delivered.message_ref,
+                    &delivered.content_hash,
+                )
+                .await
+            {
+                tracing::error!("Failed to save content hash: {:#?}", err);
+            }
+        }
+
+        info!(
+            incident_id = &incident.id,
+            success = repaired,
+            fail = total - repaired,
+            total = total,
+            "Repaired stale messages",
+        );
+    }
+}
+
+/// Whether an incident is unresolved or was resolved recently enough that
+/// its messages may still be changing
+fn is_recent(incident: &Incident) -> bool {
+    if !matches!(
+        incident.status,
+        IncidentStatus::Resolved | IncidentStatus::Postmortem
+    ) {
+        return true;
+    }
+
+    let resolved_at = incident.resolved_at.unwrap_or(incident.updated_at);
+    Utc::now().signed_duration_since(resolved_at).num_hours()
+        < RESOLVED_WINDOW_HOURS
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        db::{MessageFormat, SinkKind},
+        sinks::{
+            testing::{destination, incident, TestServer},
+            SlackBotSink,
+            TeamsSink,
+            WebhookSink,
+        },
+    };
+
+    #[tokio::test]
+    async fn repairs_once() {
+        let server = TestServer::start();
+        let mut sinks = Sinks::new(None);
+        sinks.register(
+            SinkKind::SlackBot,
+            SlackBotSink::new("xoxb-token".to_string(), &server.url),
+        );
+        sinks.register(SinkKind::Teams, TeamsSink::default());
+        sinks.register(SinkKind::Webhook, WebhookSink::default());
+
+        let incident = incident();
+        let branding = Branding::default();
+        let notification = Notification {
+            incident: &incident,
+            kind: NotificationKind::Edit,
+            update: None,
+            branding: branding.with_overrides(None),
+            format: MessageFormat::default(),
+            role_pings: vec![],
+        };
+
+        let slack =
+            destination(SinkKind::SlackBot, json!({ "channel": "C123" }));
+        let teams = destination(
+            SinkKind::Teams,
+            json!({ "webhook_url": format!("{}/teams", server.url) }),
+        );
+        let webhook = destination(
+            SinkKind::Webhook,
+            json!({ "url": format!("{}/webhook", server.url), "secret": "s" }),
+        );
+        let old_hash = "out of date".to_string();
+
+        // messages that can't be edited, and webhook deliveries, are never
+        // repaired since nothing would change
+        assert!(!sinks.needs_repair(&teams, &notification, Some(&old_hash)));
+        assert!(!sinks.needs_repair(&webhook, &notification, Some(&old_hash)));
+        assert!(sinks.needs_repair(&slack, &notification, Some(&old_hash)));
+
+        let message_ref = json!({ "channel": "C123", "ts": "1.2" });
+        server.respond(200, json!({ "ok": true }));
+        let delivered = sinks
+            .edit_or_create(&slack, &message_ref, &notification)
+            .await
+            .unwrap();
+
+        assert!(!sinks.needs_repair(
+            &slack,
+            &notification,
+            Some(&delivered.content_hash)
+        ));
+        assert_eq!(server.requests().len(), 1);
+    }
+}
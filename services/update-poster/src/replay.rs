@@ -0,0 +1,123 @@
+use std::{
+    collections::VecDeque,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    sync::Mutex,
+};
+
+use chrono::{DateTime, Utc};
+use futures::{future::BoxFuture, FutureExt};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::SourceError,
+    statuspage::{IncidentSource, Incidents},
+};
+
+/// One fetch of the status page, stored as a line of JSON
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Snapshot {
+    pub fetched_at: DateTime<Utc>,
+
+    #[serde(flatten)]
+    pub incidents: Incidents,
+}
+
+/// Saves everything fetched from another source, so it can be replayed
+/// later with [`Replay`]
+pub struct Recorder {
+    source: Box<dyn IncidentSource>,
+    output: Mutex<File>,
+}
+
+impl Recorder {
+    /// Records `source` by appending to the file at `path`
+    pub fn new(
+        source: Box<dyn IncidentSource>,
+        path: &str,
+    ) -> io::Result<Self> {
+        let output = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            source,
+            output: Mutex::new(output),
+        })
+    }
+
+    fn record(&self, incidents: &Incidents) -> io::Result<()> {
+        let snapshot = Snapshot {
+            fetched_at: Utc::now(),
+            incidents: incidents.clone(),
+        };
+
+        let mut line = serde_json::to_string(&snapshot)?;
+        line.push('\n');
+
+        self.output.lock().unwrap().write_all(line.as_bytes())
+    }
+}
+
+impl IncidentSource for Recorder {
+    fn get_all_incidents(
+        &self,
+    ) -> BoxFuture<'_, Result<Incidents, SourceError>> {
+        async move {
+            let incidents = self.source.get_all_incidents().await?;
+
+            if let Err(err) = self.record(&incidents) {
+                tracing::error!("Failed to record incidents: {:#?}", err);
+            }
+
+            Ok(incidents)
+        }
+        .boxed()
+    }
+}
+
+/// Plays back snapshots saved by [`Recorder`], one per fetch. Once every
+/// snapshot was returned, fetches fail with [`SourceError::ReplayFinished`]
+pub struct Replay {
+    snapshots: Mutex<VecDeque<Snapshot>>,
+}
+
+impl Replay {
+    pub fn new(snapshots: impl IntoIterator<Item = Snapshot>) -> Self {
+        Self {
+            snapshots: Mutex::new(snapshots.into_iter().collect()),
+        }
+    }
+
+    /// Loads a recording made by [`Recorder`]
+    pub fn load(path: &str) -> io::Result<Self> {
+        let snapshots = fs::read_to_string(path)?
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(n, line)| {
+                serde_json::from_str(line).map_err(|err| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{}:{}: {}", path, n + 1, err),
+                    )
+                })
+            })
+            .collect::<io::Result<Vec<Snapshot>>>()?;
+
+        Ok(Self::new(snapshots))
+    }
+}
+
+impl IncidentSource for Replay {
+    fn get_all_incidents(
+        &self,
+    ) -> BoxFuture<'_, Result<Incidents, SourceError>> {
+        let snapshot = self.snapshots.lock().unwrap().pop_front();
+
+        async move {
+            snapshot
+                .map(|s| s.incidents)
+                .ok_or(SourceError::ReplayFinished)
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,329 @@
+use std::{
+    cmp::Reverse,
+    collections::{hash_map::DefaultHasher, HashMap},
+    future::Future,
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use futures::{stream::FuturesUnordered, StreamExt};
+use tokio::{
+    sync::{Notify, Semaphore},
+    time::{self, Instant},
+};
+use tracing::warn;
+
+use crate::{
+    db::SinkKind,
+    error::ApplicationError,
+    statuspage::{StatusIndicator, Update},
+};
+
+/// Discord allows 50 requests per second across the whole bot
+const GLOBAL_LIMIT: (u32, Duration) = (50, Duration::from_secs(1));
+
+/// Webhooks and channels allow 5 messages per 2 seconds
+const ROUTE_LIMIT: (u32, Duration) = (5, Duration::from_secs(2));
+
+/// Other services are assumed to allow about one message per second to each
+/// destination
+const SINK_ROUTE_LIMIT: (u32, Duration) = (1, Duration::from_secs(1));
+
+/// Where a message is delivered to. Each route is rate limited separately.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Route {
+    Channel(i64),
+    Webhook(i64),
+
+    /// A destination outside of Discord, identified by a hash of its address
+    Sink(SinkKind, u64),
+}
+
+impl Route {
+    pub fn new(channel_id: i64, webhook_id: Option<i64>) -> Self {
+        match webhook_id {
+            Some(id) => Self::Webhook(id),
+            None => Self::Channel(channel_id),
+        }
+    }
+
+    /// A route for `sink`, where `address` is whatever messages are sent to,
+    /// like a URL or channel
+    pub fn sink(sink: SinkKind, address: &str) -> Self {
+        let mut hasher = DefaultHasher::new();
+        address.hash(&mut hasher);
+
+        Self::Sink(sink, hasher.finish())
+    }
+
+    fn limit(&self) -> (u32, Duration) {
+        match self {
+            Self::Channel(_) | Self::Webhook(_) => ROUTE_LIMIT,
+            Self::Sink(..) => SINK_ROUTE_LIMIT,
+        }
+    }
+
+    /// Whether messages on this route count against Discord's global limit
+    fn is_discord(&self) -> bool {
+        matches!(self, Self::Channel(_) | Self::Webhook(_))
+    }
+}
+
+struct Bucket {
+    capacity: u32,
+    period: Duration,
+    remaining: u32,
+    reset_at: Instant,
+}
+
+impl Bucket {
+    fn new((capacity, period): (u32, Duration)) -> Self {
+        Self {
+            capacity,
+            period,
+            remaining: capacity,
+            reset_at: Instant::now() + period,
+        }
+    }
+
+    /// Takes a token, or returns how long to wait until one is available
+    fn take(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+
+        if now >= self.reset_at {
+            self.remaining = self.capacity;
+            self.reset_at = now + self.period;
+        }
+
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            Ok(())
+        } else {
+            Err(self.reset_at - now)
+        }
+    }
+
+    /// Whether the bucket would be full by now, so dropping it loses nothing
+    fn is_idle(&self) -> bool {
+        Instant::now() >= self.reset_at
+    }
+
+    fn pause(&mut self, duration: Duration) {
+        self.remaining = 0;
+        self.reset_at = self.reset_at.max(Instant::now() + duration);
+    }
+}
+
+/// Runs deliveries with bounded concurrency while staying under Discord's
+/// global and per-route rate limits
+pub struct Scheduler {
+    semaphore: Semaphore,
+    global: Mutex<Bucket>,
+    routes: Mutex<HashMap<Route, Bucket>>,
+
+    /// Set when shutting down, so queued jobs stop waiting for their turn
+    closed: AtomicBool,
+    close_notify: Notify,
+}
+
+impl Scheduler {
+    pub fn new(concurrency: usize) -> Self {
+        Self {
+            semaphore: Semaphore::new(concurrency),
+            global: Mutex::new(Bucket::new(GLOBAL_LIMIT)),
+            routes: Mutex::new(HashMap::new()),
+            closed: AtomicBool::new(false),
+            close_notify: Notify::new(),
+        }
+    }
+
+    /// Stops waiting on concurrency and rate limits, so every queued job
+    /// runs right away. Used on shutdown together with [`Sinks::close`] to
+    /// make queued deliveries fail fast instead of being sent.
+    ///
+    /// [`Sinks::close`]: crate::sinks::Sinks::close
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.semaphore.close();
+        self.close_notify.notify_waiters();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Runs every job, returning their results in the order they finish
+    pub async fn run<T, S, F>(
+        &self,
+        jobs: impl IntoIterator<Item = (Route, F)>,
+    ) -> Vec<(Result<T, ApplicationError>, S)>
+    where
+        F: Future<Output = (Result<T, ApplicationError>, S)>,
+    {
+        // routes nothing was sent to for a while would otherwise be kept
+        // around forever
+        self.routes
+            .lock()
+            .unwrap()
+            .retain(|_, bucket| !bucket.is_idle());
+
+        // every job waits for its route at once, and only takes a permit once
+        // it can be sent, so jobs waiting on a busy route don't hold up others
+        jobs.into_iter()
+            .map(|(route, job)| self.run_job(route, job))
+            .collect::<FuturesUnordered<_>>()
+            .collect()
+            .await
+    }
+
+    async fn run_job<T, S, F>(
+        &self,
+        route: Route,
+        job: F,
+    ) -> (Result<T, ApplicationError>, S)
+    where
+        F: Future<Output = (Result<T, ApplicationError>, S)>,
+    {
+        self.wait_for_route(route).await;
+        // the semaphore is only closed on shutdown
+        let _permit = self.semaphore.acquire().await.ok();
+
+        let (res, s) = job.await;
+        if let Err(err) = &res {
+            self.handle_ratelimit(route, err);
+        }
+
+        (res, s)
+    }
+
+    async fn wait_for_route(&self, route: Route) {
+        loop {
+            let wait = self
+                .routes
+                .lock()
+                .unwrap()
+                .entry(route)
+                .or_insert_with(|| Bucket::new(route.limit()))
+                .take();
+
+            match wait {
+                Ok(_) => break,
+                Err(_) if self.is_closed() => return,
+                Err(wait) => self.sleep(wait).await,
+            }
+        }
+
+        if !route.is_discord() {
+            return;
+        }
+
+        loop {
+            let wait = self.global.lock().unwrap().take();
+
+            match wait {
+                Ok(_) => break,
+                Err(_) if self.is_closed() => return,
+                Err(wait) => self.sleep(wait).await,
+            }
+        }
+    }
+
+    /// Sleeps until a bucket resets, or returns early if the scheduler is
+    /// closed
+    async fn sleep(&self, duration: Duration) {
+        let closed = self.close_notify.notified();
+        if self.is_closed() {
+            return;
+        }
+
+        tokio::select! {
+            _ = time::sleep(duration) => {},
+            _ = closed => {},
+        }
+    }
+
+    fn handle_ratelimit(&self, route: Route, err: &ApplicationError) {
+        let Some((global, retry_after)) = err.ratelimit() else {
+            return;
+        };
+
+        warn!(
+            global,
+            ?route,
+            retry_after = retry_after.as_secs_f64(),
+            "Hit a rate limit"
+        );
+
+        if global {
+            self.global.lock().unwrap().pause(retry_after);
+        } else if let Some(bucket) = self.routes.lock().unwrap().get_mut(&route)
+        {
+            bucket.pause(retry_after);
+        }
+    }
+}
+
+/// How urgently an update should be delivered. Higher is sent first.
+pub fn get_update_priority(update: &Update) -> impl Ord {
+    let (incident, kind) = match update {
+        Update::Created(i) => (i, 3),
+        Update::UpdateCreated(i, _) => (i, 2),
+        Update::UpdateModified(i, _) => (i, 1),
+        Update::PostmortemPublished(i, _) => (i, 0),
+    };
+
+    let impact = match incident.impact {
+        StatusIndicator::Critical => 4,
+        StatusIndicator::Major => 3,
+        StatusIndicator::Minor => 2,
+        StatusIndicator::Maintenance => 1,
+        StatusIndicator::None => 0,
+    };
+
+    (impact, kind)
+}
+
+/// Sorts updates so the most urgent are delivered first
+pub fn sort_by_priority(updates: &mut [Update]) {
+    updates.sort_by_cached_key(|u| Reverse(get_update_priority(u)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn busy_routes_dont_hold_up_others() {
+        let scheduler = Scheduler::new(1);
+        let busy = Route::sink(SinkKind::Webhook, "busy");
+        let free = Route::sink(SinkKind::Webhook, "free");
+
+        let jobs = [(busy, "first"), (busy, "second"), (free, "third")]
+            .map(|(route, name)| (route, async move { (Ok(()), name) }));
+
+        let order: Vec<_> = scheduler
+            .run(jobs)
+            .await
+            .into_iter()
+            .map(|(_, name)| name)
+            .collect();
+
+        assert_eq!(order, ["first", "third", "second"]);
+    }
+
+    #[test]
+    fn idle_buckets() {
+        let mut bucket = Bucket::new(SINK_ROUTE_LIMIT);
+        assert!(!bucket.is_idle());
+
+        bucket.reset_at = Instant::now();
+        assert!(bucket.is_idle());
+
+        bucket.pause(Duration::from_secs(5));
+        assert!(!bucket.is_idle());
+    }
+}
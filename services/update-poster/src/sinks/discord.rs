@@ -0,0 +1,213 @@
+use std::sync::Arc;
+
+use futures::{future::BoxFuture, FutureExt};
+use twilight_model::id::{marker::MessageMarker, Id};
+
+use super::{Destination, Notification, NotificationKind, NotificationSink};
+use crate::{
+    discord::Discord,
+    error::{ApplicationError, Result},
+    message::MessageBody,
+    scheduler::Route,
+};
+
+/// Sends messages to the subscription's channel as the bot user
+pub struct DiscordBotSink {
+    discord: Arc<Discord>,
+}
+
+impl DiscordBotSink {
+    pub fn new(discord: Arc<Discord>) -> Self {
+        Self { discord }
+    }
+}
+
+impl NotificationSink for DiscordBotSink {
+    type MessageRef = Id<MessageMarker>;
+    type Payload = MessageBody;
+
+    fn render(&self, notification: &Notification) -> MessageBody {
+        render(notification)
+    }
+
+    fn content_hash(&self, body: &MessageBody) -> String {
+        body.content_hash()
+    }
+
+    fn route(&self, destination: &Destination) -> Route {
+        Route::Channel(destination.channel_id.unwrap_or_default())
+    }
+
+    fn create<'a>(
+        &'a self,
+        destination: &'a Destination,
+        body: &'a MessageBody,
+    ) -> BoxFuture<'a, Result<Id<MessageMarker>>> {
+        async move {
+            let channel_id = destination
+                .channel_id
+                .ok_or_else(|| missing_channel(destination))?;
+            self.discord.create_message(channel_id, None, body).await
+        }
+        .boxed()
+    }
+
+    fn edit<'a>(
+        &'a self,
+        destination: &'a Destination,
+        message: &'a Id<MessageMarker>,
+        body: &'a MessageBody,
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let channel_id = destination
+                .channel_id
+                .ok_or_else(|| missing_channel(destination))?;
+            self.discord
+                .update_message(channel_id, None, *message, body)
+                .await
+        }
+        .boxed()
+    }
+
+    fn delete<'a>(
+        &'a self,
+        destination: &'a Destination,
+        message: &'a Id<MessageMarker>,
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let channel_id = destination
+                .channel_id
+                .ok_or_else(|| missing_channel(destination))?;
+            self.discord
+                .delete_message(channel_id, None, *message)
+                .await
+        }
+        .boxed()
+    }
+}
+
+/// Sends messages through the webhook set up for the subscription
+pub struct DiscordWebhookSink {
+    discord: Arc<Discord>,
+}
+
+impl DiscordWebhookSink {
+    pub fn new(discord: Arc<Discord>) -> Self {
+        Self { discord }
+    }
+}
+
+impl NotificationSink for DiscordWebhookSink {
+    type MessageRef = Id<MessageMarker>;
+    type Payload = MessageBody;
+
+    fn render(&self, notification: &Notification) -> MessageBody {
+        render(notification)
+    }
+
+    fn content_hash(&self, body: &MessageBody) -> String {
+        body.content_hash()
+    }
+
+    fn route(&self, destination: &Destination) -> Route {
+        Route::new(
+            destination.channel_id.unwrap_or_default(),
+            destination.webhook_id,
+        )
+    }
+
+    fn create<'a>(
+        &'a self,
+        destination: &'a Destination,
+        body: &'a MessageBody,
+    ) -> BoxFuture<'a, Result<Id<MessageMarker>>> {
+        async move {
+            let channel_id = destination
+                .channel_id
+                .ok_or_else(|| missing_channel(destination))?;
+            let webhook = webhook(destination)
+                .ok_or_else(|| missing_webhook(destination))?;
+            self.discord
+                .create_message(channel_id, Some(webhook), body)
+                .await
+        }
+        .boxed()
+    }
+
+    fn edit<'a>(
+        &'a self,
+        destination: &'a Destination,
+        message: &'a Id<MessageMarker>,
+        body: &'a MessageBody,
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let channel_id = destination
+                .channel_id
+                .ok_or_else(|| missing_channel(destination))?;
+            let webhook = webhook(destination)
+                .ok_or_else(|| missing_webhook(destination))?;
+            self.discord
+                .update_message(channel_id, Some(webhook), *message, body)
+                .await
+        }
+        .boxed()
+    }
+
+    fn delete<'a>(
+        &'a self,
+        destination: &'a Destination,
+        message: &'a Id<MessageMarker>,
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let channel_id = destination
+                .channel_id
+                .ok_or_else(|| missing_channel(destination))?;
+            let webhook = webhook(destination)
+                .ok_or_else(|| missing_webhook(destination))?;
+            self.discord
+                .delete_message(channel_id, Some(webhook), *message)
+                .await
+        }
+        .boxed()
+    }
+}
+
+fn render(notification: &Notification) -> MessageBody {
+    let Notification {
+        incident,
+        branding,
+        format,
+        role_pings,
+        ..
+    } = notification;
+
+    match notification.kind {
+        NotificationKind::Post(update) => {
+            MessageBody::post(incident, update, branding, *format, role_pings)
+        },
+        NotificationKind::Edit => {
+            MessageBody::edit(incident, branding, *format, role_pings)
+        },
+        NotificationKind::Postmortem(update) => {
+            MessageBody::postmortem(incident, update, branding, *format)
+        },
+    }
+}
+
+/// The webhook's ID and token, which every webhook subscription should have
+fn webhook(destination: &Destination) -> Option<(i64, &str)> {
+    match (destination.webhook_id, &destination.webhook_token) {
+        (Some(id), Some(token)) => Some((id, token)),
+        _ => None,
+    }
+}
+
+fn missing_webhook(destination: &Destination) -> ApplicationError {
+    destination.invalid("missing webhook id or token")
+}
+
+/// Every Discord subscription has a channel, but the column can be null
+/// since other sinks don't
+fn missing_channel(destination: &Destination) -> ApplicationError {
+    destination.invalid("missing channel id")
+}
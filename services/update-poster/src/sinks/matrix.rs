@@ -0,0 +1,462 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use chrono::Utc;
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::{Client as ReqwestClient, Method, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::types::JsonValue;
+
+use super::{Destination, Notification, NotificationKind, NotificationSink};
+use crate::{
+    db::SinkKind,
+    error::{ApplicationError, Result},
+    matrix::MatrixMessage,
+    scheduler::Route,
+};
+
+const SINK: SinkKind = SinkKind::Matrix;
+
+/// `sink_config` of a `matrix` subscription
+#[derive(Deserialize)]
+struct MatrixConfig {
+    /// ID of the room to post in, like `!abc:example.org`. The account has to
+    /// have joined it already
+    room_id: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MatrixEvent {
+    pub room_id: String,
+    pub event_id: String,
+}
+
+/// Posts to Matrix rooms through the client-server API, editing messages
+/// with `m.replace` relations
+pub struct MatrixSink {
+    http: ReqwestClient,
+    homeserver_url: Url,
+    access_token: String,
+
+    /// Transaction IDs only have to be unique for the access token. The time
+    /// the sink was created keeps them unique across restarts
+    txn_prefix: i64,
+    txn_counter: AtomicU64,
+}
+
+impl MatrixSink {
+    pub fn new(homeserver_url: Url, access_token: String) -> Self {
+        Self {
+            http: ReqwestClient::new(),
+            homeserver_url,
+            access_token,
+            txn_prefix: Utc::now().timestamp_millis(),
+            txn_counter: AtomicU64::new(0),
+        }
+    }
+
+    fn endpoint(&self, path: &[&str]) -> Url {
+        let mut url = self.homeserver_url.clone();
+        url.path_segments_mut()
+            .expect("homeserver urls are always http(s)")
+            .pop_if_empty()
+            .extend(["_matrix", "client", "v3"])
+            .extend(path);
+
+        url
+    }
+
+    fn next_txn_id(&self) -> String {
+        let n = self.txn_counter.fetch_add(1, Ordering::Relaxed);
+        format!("{}.{}", self.txn_prefix, n)
+    }
+
+    /// Sends an `m.room.message` event, returning its ID
+    async fn send(&self, room_id: &str, content: JsonValue) -> Result<String> {
+        let txn_id = self.next_txn_id();
+        let url = self.endpoint(&[
+            "rooms",
+            room_id,
+            "send",
+            "m.room.message",
+            &txn_id,
+        ]);
+
+        let res = self.call(Method::PUT, url, content).await?;
+        res["event_id"].as_str().map(str::to_string).ok_or_else(|| {
+            ApplicationError::SinkApiError {
+                sink: SINK,
+                error: "response is missing the event id".to_string(),
+            }
+        })
+    }
+
+    /// Matrix answers `M_NOT_FOUND` for unknown rooms and events alike. An
+    /// event is only gone if the room it was sent in is still joined, since
+    /// a new message can't be sent otherwise either
+    async fn check_event_missing(
+        &self,
+        room_id: &str,
+        err: ApplicationError,
+    ) -> ApplicationError {
+        if !matches!(err, ApplicationError::MessageNotFound { .. }) {
+            return err;
+        }
+
+        match self.is_joined(room_id).await {
+            Ok(true) => err,
+            Ok(false) => not_joined(room_id),
+            Err(err) => err,
+        }
+    }
+
+    /// https://spec.matrix.org/v1.8/client-server-api/#get_matrixclientv3joined_rooms
+    async fn is_joined(&self, room_id: &str) -> Result<bool> {
+        let res = self
+            .call(
+                Method::GET,
+                self.endpoint(&["joined_rooms"]),
+                JsonValue::Null,
+            )
+            .await?;
+
+        Ok(res["joined_rooms"]
+            .as_array()
+            .is_some_and(|rooms| rooms.iter().any(|r| r == room_id)))
+    }
+
+    /// Makes a request, turning Matrix's error responses into errors
+    /// https://spec.matrix.org/v1.8/client-server-api/#standard-error-response
+    async fn call(
+        &self,
+        method: Method,
+        url: Url,
+        body: JsonValue,
+    ) -> Result<JsonValue> {
+        let mut req = self
+            .http
+            .request(method, url)
+            .bearer_auth(&self.access_token);
+
+        if !body.is_null() {
+            req = req.json(&body);
+        }
+
+        let res = req.send().await.map_err(|source| {
+            ApplicationError::SinkRequestError { sink: SINK, source }
+        })?;
+
+        let status = res.status();
+        let body: JsonValue = res.json().await.unwrap_or_default();
+
+        if status.is_success() {
+            return Ok(body);
+        }
+
+        let errcode = body["errcode"].as_str().unwrap_or_default();
+        let error = format!(
+            "{} {}: {}",
+            status,
+            errcode,
+            body["error"].as_str().unwrap_or_default()
+        );
+
+        Err(match errcode {
+            "M_LIMIT_EXCEEDED" => ApplicationError::SinkRatelimited {
+                sink: SINK,
+                retry_after: Duration::from_millis(
+                    body["retry_after_ms"].as_u64().unwrap_or(1000),
+                ),
+            },
+            "M_NOT_FOUND" => ApplicationError::MessageNotFound { sink: SINK },
+            _ if status == StatusCode::TOO_MANY_REQUESTS => {
+                ApplicationError::SinkRatelimited {
+                    sink: SINK,
+                    retry_after: Duration::from_secs(1),
+                }
+            },
+            _ if status.is_server_error() => {
+                ApplicationError::SinkServerError { sink: SINK, error }
+            },
+            _ => ApplicationError::SinkApiError { sink: SINK, error },
+        })
+    }
+}
+
+fn not_joined(room_id: &str) -> ApplicationError {
+    ApplicationError::SinkApiError {
+        sink: SINK,
+        error: format!("not in room {}", room_id),
+    }
+}
+
+impl NotificationSink for MatrixSink {
+    type MessageRef = MatrixEvent;
+    type Payload = MatrixMessage;
+
+    fn render(&self, notification: &Notification) -> MatrixMessage {
+        let Notification {
+            incident, branding, ..
+        } = notification;
+
+        match notification.kind {
+            NotificationKind::Post(update) => {
+                MatrixMessage::post(incident, update, branding)
+            },
+            NotificationKind::Edit => MatrixMessage::edit(incident, branding),
+            NotificationKind::Postmortem(update) => {
+                MatrixMessage::postmortem(incident, update, branding)
+            },
+        }
+    }
+
+    fn route(&self, destination: &Destination) -> Route {
+        let room_id = destination
+            .config::<MatrixConfig>()
+            .map(|c| c.room_id)
+            .unwrap_or_default();
+
+        Route::sink(SINK, &room_id)
+    }
+
+    fn create<'a>(
+        &'a self,
+        destination: &'a Destination,
+        message: &'a MatrixMessage,
+    ) -> BoxFuture<'a, Result<MatrixEvent>> {
+        async move {
+            let config: MatrixConfig =
+                destination.config().map_err(|e| destination.invalid(e))?;
+
+            let event_id = self
+                .send(&config.room_id, json!(message))
+                .await
+                .map_err(|err| match err {
+                    ApplicationError::MessageNotFound { .. } => {
+                        not_joined(&config.room_id)
+                    },
+                    err => err,
+                })?;
+
+            Ok(MatrixEvent {
+                room_id: config.room_id,
+                event_id,
+            })
+        }
+        .boxed()
+    }
+
+    /// Clients that don't understand edits show the fallback, which is the
+    /// new content marked with a `*`
+    /// https://spec.matrix.org/v1.8/client-server-api/#event-replacements
+    fn edit<'a>(
+        &'a self,
+        _destination: &'a Destination,
+        event: &'a MatrixEvent,
+        message: &'a MatrixMessage,
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let content = json!({
+                "msgtype": message.msgtype,
+                "body": format!("* {}", message.body),
+                "format": message.format,
+                "formatted_body": format!("* {}", message.formatted_body),
+                "m.new_content": message,
+                "m.relates_to": {
+                    "rel_type": "m.replace",
+                    "event_id": event.event_id,
+                },
+            });
+
+            match self.send(&event.room_id, content).await {
+                Ok(_) => Ok(()),
+                Err(err) => {
+                    Err(self.check_event_missing(&event.room_id, err).await)
+                },
+            }
+        }
+        .boxed()
+    }
+
+    fn delete<'a>(
+        &'a self,
+        _destination: &'a Destination,
+        event: &'a MatrixEvent,
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let txn_id = self.next_txn_id();
+            let url = self.endpoint(&[
+                "rooms",
+                &event.room_id,
+                "redact",
+                &event.event_id,
+                &txn_id,
+            ]);
+
+            match self.call(Method::PUT, url, json!({})).await {
+                Ok(_) => Ok(()),
+                Err(err) => {
+                    Err(self.check_event_missing(&event.room_id, err).await)
+                },
+            }
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::http::Method;
+    use chrono::Duration as ChronoDuration;
+
+    use super::*;
+    use crate::{
+        branding::Branding,
+        sinks::testing::{destination, incident, TestServer},
+        statuspage::IncidentUpdate,
+        util::get_timeline_updates,
+    };
+
+    fn sink(server: &TestServer) -> MatrixSink {
+        MatrixSink::new(server.url.parse().unwrap(), "syt_token".to_string())
+    }
+
+    fn message() -> MatrixMessage {
+        MatrixMessage::edit(&incident(), &Branding::default())
+    }
+
+    fn event() -> MatrixEvent {
+        MatrixEvent {
+            room_id: "!room:example.org".to_string(),
+            event_id: "$event".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn posts_and_edits() {
+        let server = TestServer::start();
+        let sink = sink(&server);
+        let destination = destination(
+            SinkKind::Matrix,
+            json!({ "room_id": "!room:example.org" }),
+        );
+
+        server.respond(200, json!({ "event_id": "$event" }));
+        let sent = sink.create(&destination, &message()).await.unwrap();
+        assert_eq!(sent.room_id, "!room:example.org");
+        assert_eq!(sent.event_id, "$event");
+
+        server.respond(200, json!({ "event_id": "$edit" }));
+        sink.edit(&destination, &sent, &message()).await.unwrap();
+        sink.delete(&destination, &sent).await.unwrap();
+
+        let requests = server.requests();
+        let txn = format!("{}.", sink.txn_prefix);
+        assert_eq!(requests[0].method, Method::PUT);
+        assert_eq!(
+            requests[0].path,
+            format!(
+                "/_matrix/client/v3/rooms/!room:example.org/send/m.room.message/{}0",
+                txn
+            )
+        );
+        assert_eq!(requests[0].headers["authorization"], "Bearer syt_token");
+        assert_eq!(requests[0].body["msgtype"], "m.notice");
+        assert_eq!(requests[0].body["body"], message().body);
+
+        assert!(requests[1].path.ends_with(&format!("{}1", txn)));
+        assert_eq!(requests[1].body["body"], format!("* {}", message().body));
+        assert_eq!(requests[1].body["m.new_content"]["body"], message().body);
+        assert_eq!(
+            requests[1].body["m.relates_to"],
+            json!({ "rel_type": "m.replace", "event_id": "$event" })
+        );
+
+        assert_eq!(
+            requests[2].path,
+            format!(
+                "/_matrix/client/v3/rooms/!room:example.org/redact/$event/{}2",
+                txn
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn errors() {
+        let server = TestServer::start();
+        let sink = sink(&server);
+        let destination = destination(
+            SinkKind::Matrix,
+            json!({ "room_id": "!room:example.org" }),
+        );
+
+        server.respond(
+            429,
+            json!({ "errcode": "M_LIMIT_EXCEEDED", "retry_after_ms": 2500 }),
+        );
+        let err = sink.create(&destination, &message()).await.unwrap_err();
+        assert_eq!(err.ratelimit(), Some((false, Duration::from_millis(2500))));
+
+        // homeservers word these differently, so only the room membership
+        // decides what was missing
+        let not_found = json!({ "errcode": "M_NOT_FOUND", "error": "" });
+        let joined = json!({ "joined_rooms": ["!room:example.org"] });
+
+        server.respond(404, not_found.clone());
+        server.respond(200, joined.clone());
+        let err = sink.edit(&destination, &event(), &message()).await;
+        assert!(err.unwrap_err().is_unknown_message());
+
+        server.respond(404, not_found.clone());
+        server.respond(200, joined);
+        let err = sink.delete(&destination, &event()).await.unwrap_err();
+        assert!(err.is_unknown_message());
+
+        let requests = server.requests();
+        assert_eq!(requests[2].method, Method::GET);
+        assert_eq!(requests[2].path, "/_matrix/client/v3/joined_rooms");
+
+        // the room is gone, so the message can't be posted again either
+        server.respond(404, not_found.clone());
+        server.respond(200, json!({ "joined_rooms": [] }));
+        let err = sink.edit(&destination, &event(), &message()).await;
+        assert!(matches!(
+            err.unwrap_err(),
+            ApplicationError::SinkApiError { .. }
+        ));
+
+        server.respond(404, not_found);
+        let err = sink.create(&destination, &message()).await.unwrap_err();
+        assert!(!err.is_unknown_message());
+    }
+
+    #[test]
+    fn edits_fit_in_an_event() {
+        let mut incident = incident();
+        let latest = get_timeline_updates(&incident)[0].clone();
+
+        incident.incident_updates = (0..100)
+            .map(|i| IncidentUpdate {
+                id: format!("update{}", i),
+                body: "<&>".repeat(10000),
+                created_at: latest.created_at - ChronoDuration::minutes(i),
+                ..latest.clone()
+            })
+            .collect();
+
+        let message = MatrixMessage::edit(&incident, &Branding::default());
+        let content = json!({
+            "body": format!("* {}", message.body),
+            "formatted_body": format!("* {}", message.formatted_body),
+            "m.new_content": message,
+        });
+
+        assert!(message.formatted_body.contains("&lt;&amp;&gt;"));
+        assert!(content.to_string().len() < 65536);
+    }
+}
@@ -0,0 +1,560 @@
+//! Places notifications are delivered to. Each subscription refers to one
+//! sink by its [`SinkKind`], and the sink decides how messages are rendered,
+//! sent and identified afterwards.
+
+mod discord;
+mod email;
+mod matrix;
+mod push;
+mod slack;
+mod teams;
+mod telegram;
+#[cfg(test)]
+pub mod testing;
+mod webhook;
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use futures::{
+    future::{self, BoxFuture},
+    FutureExt,
+};
+use reqwest::{header::RETRY_AFTER, Response, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::types::JsonValue;
+use tracing::info;
+
+pub use self::{
+    discord::{DiscordBotSink, DiscordWebhookSink},
+    email::EmailSink,
+    matrix::MatrixSink,
+    push::{GotifySink, NtfySink},
+    slack::{SlackBotSink, SlackWebhookSink},
+    teams::TeamsSink,
+    telegram::TelegramSink,
+    webhook::WebhookSink,
+};
+use crate::{
+    branding::Branding,
+    db::{MessageFormat, SinkKind},
+    dry_run::DryRunSink,
+    error::{ApplicationError, Result},
+    scheduler::Route,
+    statuspage::{Incident, IncidentUpdate, Update},
+};
+
+/// What a notification shows
+#[derive(Clone, Copy, Debug)]
+pub enum NotificationKind<'a> {
+    /// A single incident update, sent as its own message
+    Post(&'a IncidentUpdate),
+
+    /// The whole incident, kept up to date by editing one message
+    Edit,
+
+    /// A published postmortem, which is always sent as its own message
+    Postmortem(&'a IncidentUpdate),
+}
+
+/// Everything a sink needs to render a message for one subscription
+#[derive(Clone, Debug)]
+pub struct Notification<'a> {
+    pub incident: &'a Incident,
+    pub kind: NotificationKind<'a>,
+
+    /// The change being delivered. Not set when the reconciler repairs
+    /// messages, since they're re-rendered from the incident alone
+    pub update: Option<&'a Update>,
+
+    /// The subscription's branding, with its overrides applied
+    pub branding: Cow<'a, Branding>,
+    pub format: MessageFormat,
+    pub role_pings: Vec<i64>,
+}
+
+/// Where a subscription's messages are delivered
+#[derive(Clone, Debug)]
+pub struct Destination {
+    pub sink: SinkKind,
+
+    /// Only set for Discord subscriptions
+    pub channel_id: Option<i64>,
+    pub webhook_id: Option<i64>,
+    pub webhook_token: Option<String>,
+
+    /// Settings specific to the sink, from `subscriptions.sink_config`
+    pub config: Option<JsonValue>,
+}
+
+impl Destination {
+    /// Parses the sink specific settings
+    pub fn config<T: DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_value(self.config.clone().unwrap_or_default())
+    }
+
+    /// An error for a subscription that can't be delivered to as it's set up
+    pub fn invalid(&self, reason: impl ToString) -> ApplicationError {
+        ApplicationError::InvalidDestination {
+            sink: self.sink,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// A message that was sent or edited
+#[derive(Debug)]
+pub struct Delivered {
+    /// The sink's reference to the message, as it's stored in
+    /// `sent_updates.message_ref`
+    pub message_ref: JsonValue,
+    pub content_hash: String,
+}
+
+/// Renders and delivers notifications to one kind of destination
+pub trait NotificationSink: Send + Sync {
+    /// Identifies a sent message, so it can be edited or deleted later.
+    /// Stored as JSON between deliveries
+    type MessageRef: Serialize + DeserializeOwned + Send + Sync;
+
+    /// A rendered message
+    type Payload: Serialize + Send + Sync;
+
+    fn render(&self, notification: &Notification) -> Self::Payload;
+
+    /// A hash of everything that gets sent, used to tell whether a sent
+    /// message is out of date
+    fn content_hash(&self, payload: &Self::Payload) -> String {
+        hash_json(payload)
+    }
+
+    /// The rate limit bucket messages to `destination` count against
+    fn route(&self, destination: &Destination) -> Route;
+
+    /// Whether sent messages can be edited. If they can't, edit mode sends
+    /// the whole incident as a new message for every update instead
+    fn can_edit(&self, _destination: &Destination) -> bool {
+        true
+    }
+
+    /// Whether the reconciler can re-render sent messages and edit the ones
+    /// that are out of date
+    fn can_repair(&self, destination: &Destination) -> bool {
+        self.can_edit(destination)
+    }
+
+    fn create<'a>(
+        &'a self,
+        destination: &'a Destination,
+        payload: &'a Self::Payload,
+    ) -> BoxFuture<'a, Result<Self::MessageRef>>;
+
+    /// Replaces a message's content. Fails with an error that
+    /// [`ApplicationError::is_unknown_message`] is true for if the message
+    /// was deleted
+    fn edit<'a>(
+        &'a self,
+        destination: &'a Destination,
+        _message: &'a Self::MessageRef,
+        _payload: &'a Self::Payload,
+    ) -> BoxFuture<'a, Result<()>> {
+        unsupported(destination, "edit")
+    }
+
+    fn delete<'a>(
+        &'a self,
+        destination: &'a Destination,
+        _message: &'a Self::MessageRef,
+    ) -> BoxFuture<'a, Result<()>> {
+        unsupported(destination, "delete")
+    }
+}
+
+/// What sinks that can't edit or delete their messages fail with
+fn unsupported<'a>(
+    destination: &Destination,
+    action: &'static str,
+) -> BoxFuture<'a, Result<()>> {
+    future::ready(Err(ApplicationError::Unsupported {
+        sink: destination.sink,
+        action,
+    }))
+    .boxed()
+}
+
+/// Incoming webhooks don't say which message they created, so there's
+/// nothing to refer to
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WebhookMessage {}
+
+/// [`NotificationSink`] with its message references and payloads as JSON,
+/// so sinks of different kinds can be stored together
+trait ErasedSink: Send + Sync {
+    fn route(&self, destination: &Destination) -> Route;
+
+    fn can_edit(&self, destination: &Destination) -> bool;
+
+    fn can_repair(&self, destination: &Destination) -> bool;
+
+    /// The rendered payload and its content hash
+    fn render(&self, notification: &Notification) -> (JsonValue, String);
+
+    fn content_hash(&self, notification: &Notification) -> String;
+
+    fn create<'a>(
+        &'a self,
+        destination: &'a Destination,
+        notification: &'a Notification,
+    ) -> BoxFuture<'a, Result<Delivered>>;
+
+    fn edit<'a>(
+        &'a self,
+        destination: &'a Destination,
+        message_ref: &'a JsonValue,
+        notification: &'a Notification,
+    ) -> BoxFuture<'a, Result<Delivered>>;
+
+    fn delete<'a>(
+        &'a self,
+        destination: &'a Destination,
+        message_ref: &'a JsonValue,
+    ) -> BoxFuture<'a, Result<()>>;
+}
+
+impl<S: NotificationSink> ErasedSink for S {
+    fn route(&self, destination: &Destination) -> Route {
+        NotificationSink::route(self, destination)
+    }
+
+    fn can_edit(&self, destination: &Destination) -> bool {
+        NotificationSink::can_edit(self, destination)
+    }
+
+    fn can_repair(&self, destination: &Destination) -> bool {
+        NotificationSink::can_repair(self, destination)
+    }
+
+    fn render(&self, notification: &Notification) -> (JsonValue, String) {
+        let payload = NotificationSink::render(self, notification);
+        let json = serde_json::to_value(&payload)
+            .expect("payloads are always valid json");
+
+        (json, NotificationSink::content_hash(self, &payload))
+    }
+
+    fn content_hash(&self, notification: &Notification) -> String {
+        let payload = NotificationSink::render(self, notification);
+        NotificationSink::content_hash(self, &payload)
+    }
+
+    fn create<'a>(
+        &'a self,
+        destination: &'a Destination,
+        notification: &'a Notification,
+    ) -> BoxFuture<'a, Result<Delivered>> {
+        async move {
+            let payload = NotificationSink::render(self, notification);
+            let message =
+                NotificationSink::create(self, destination, &payload).await?;
+
+            Ok(Delivered {
+                message_ref: serde_json::to_value(message)
+                    .expect("message refs are always valid json"),
+                content_hash: NotificationSink::content_hash(self, &payload),
+            })
+        }
+        .boxed()
+    }
+
+    fn edit<'a>(
+        &'a self,
+        destination: &'a Destination,
+        message_ref: &'a JsonValue,
+        notification: &'a Notification,
+    ) -> BoxFuture<'a, Result<Delivered>> {
+        async move {
+            let message: S::MessageRef =
+                serde_json::from_value(message_ref.clone())?;
+            let payload = NotificationSink::render(self, notification);
+            NotificationSink::edit(self, destination, &message, &payload)
+                .await?;
+
+            Ok(Delivered {
+                message_ref: message_ref.clone(),
+                content_hash: NotificationSink::content_hash(self, &payload),
+            })
+        }
+        .boxed()
+    }
+
+    fn delete<'a>(
+        &'a self,
+        destination: &'a Destination,
+        message_ref: &'a JsonValue,
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let message: S::MessageRef =
+                serde_json::from_value(message_ref.clone())?;
+            NotificationSink::delete(self, destination, &message).await
+        }
+        .boxed()
+    }
+}
+
+/// Every configured sink, by the kind subscriptions refer to them with
+pub struct Sinks {
+    sinks: HashMap<SinkKind, Box<dyn ErasedSink>>,
+
+    /// Set when shutting down, after which no more messages are sent
+    closed: AtomicBool,
+
+    /// Receives messages instead of the sinks in dry-run mode
+    dry_run: Option<DryRunSink>,
+}
+
+impl Sinks {
+    pub fn new(dry_run: Option<DryRunSink>) -> Self {
+        Self {
+            sinks: HashMap::new(),
+            closed: AtomicBool::new(false),
+            dry_run,
+        }
+    }
+
+    /// Delivers messages for subscriptions with the sink `kind` to `sink`
+    pub fn register(
+        &mut self,
+        kind: SinkKind,
+        sink: impl NotificationSink + 'static,
+    ) {
+        self.sinks.insert(kind, Box::new(sink));
+    }
+
+    /// Makes every following request fail with
+    /// [`ApplicationError::ShuttingDown`] instead of being sent
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    fn get(&self, kind: SinkKind) -> Option<&dyn ErasedSink> {
+        self.sinks.get(&kind).map(|s| s.as_ref())
+    }
+
+    /// The rate limit bucket messages to `destination` count against. Falls
+    /// back to one bucket for the whole sink if it isn't configured, since
+    /// sending will fail right away anyway
+    pub fn route(&self, destination: &Destination) -> Route {
+        match self.get(destination.sink) {
+            Some(sink) => sink.route(destination),
+            None => Route::sink(destination.sink, ""),
+        }
+    }
+
+    /// Whether messages sent to `destination` can be edited
+    pub fn can_edit(&self, destination: &Destination) -> bool {
+        self.get(destination.sink)
+            .is_some_and(|sink| sink.can_edit(destination))
+    }
+
+    /// Whether a message sent to `destination` with `content_hash` no longer
+    /// matches the notification and can be repaired by editing it
+    pub fn needs_repair(
+        &self,
+        destination: &Destination,
+        notification: &Notification<'_>,
+        content_hash: Option<&String>,
+    ) -> bool {
+        // messages for sinks that aren't configured are skipped too
+        let Some(sink) = self.get(destination.sink) else {
+            return false;
+        };
+
+        sink.can_repair(destination)
+            && content_hash != Some(&sink.content_hash(notification))
+    }
+
+    /// The hash [`Delivered::content_hash`] would have, or nothing if the
+    /// sink isn't configured
+    pub fn content_hash(
+        &self,
+        destination: &Destination,
+        notification: &Notification<'_>,
+    ) -> Option<String> {
+        self.get(destination.sink)
+            .map(|sink| sink.content_hash(notification))
+    }
+
+    pub async fn create(
+        &self,
+        destination: &Destination,
+        notification: &Notification<'_>,
+    ) -> Result<Delivered> {
+        if self.is_closed() {
+            return Err(ApplicationError::ShuttingDown);
+        }
+
+        let sink = self.get(destination.sink).ok_or(
+            ApplicationError::SinkNotConfigured {
+                sink: destination.sink,
+            },
+        )?;
+
+        if let Some(dry_run) = &self.dry_run {
+            let (payload, content_hash) = sink.render(notification);
+            let message_ref =
+                dry_run.create_message(destination, &payload, &content_hash);
+
+            return Ok(Delivered {
+                message_ref,
+                content_hash,
+            });
+        }
+
+        sink.create(destination, notification).await
+    }
+
+    pub async fn edit(
+        &self,
+        destination: &Destination,
+        message_ref: &JsonValue,
+        notification: &Notification<'_>,
+    ) -> Result<Delivered> {
+        if self.is_closed() {
+            return Err(ApplicationError::ShuttingDown);
+        }
+
+        let sink = self.get(destination.sink).ok_or(
+            ApplicationError::SinkNotConfigured {
+                sink: destination.sink,
+            },
+        )?;
+
+        // the message stays as it is, and the next update is sent as a new
+        // one instead
+        if !sink.can_edit(destination) {
+            return Ok(Delivered {
+                message_ref: message_ref.clone(),
+                content_hash: sink.content_hash(notification),
+            });
+        }
+
+        if let Some(dry_run) = &self.dry_run {
+            let (payload, content_hash) = sink.render(notification);
+            dry_run.update_message(
+                destination,
+                message_ref,
+                &payload,
+                &content_hash,
+            );
+
+            return Ok(Delivered {
+                message_ref: message_ref.clone(),
+                content_hash,
+            });
+        }
+
+        sink.edit(destination, message_ref, notification).await
+    }
+
+    /// Edits a message, or sends a new one if the message was deleted. The
+    /// returned reference points at the new message in that case, so the
+    /// caller can point future edits at it
+    pub async fn edit_or_create(
+        &self,
+        destination: &Destination,
+        message_ref: &JsonValue,
+        notification: &Notification<'_>,
+    ) -> Result<Delivered> {
+        match self.edit(destination, message_ref, notification).await {
+            Err(err) if err.is_unknown_message() => {
+                info!(
+                    sink = ?destination.sink,
+                    channel_id = ?destination.channel_id,
+                    message_ref = message_ref.to_string(),
+                    "Message was deleted, sending a new one",
+                );
+
+                self.create(destination, notification).await
+            },
+            res => res,
+        }
+    }
+
+    pub async fn delete(
+        &self,
+        destination: &Destination,
+        message_ref: &JsonValue,
+    ) -> Result<()> {
+        if self.is_closed() {
+            return Err(ApplicationError::ShuttingDown);
+        }
+
+        let sink = self.get(destination.sink).ok_or(
+            ApplicationError::SinkNotConfigured {
+                sink: destination.sink,
+            },
+        )?;
+
+        if let Some(dry_run) = &self.dry_run {
+            dry_run.delete_message(destination, message_ref);
+            return Ok(());
+        }
+
+        sink.delete(destination, message_ref).await
+    }
+}
+
+/// Hashes the JSON a payload serializes to
+pub fn hash_json(payload: &impl Serialize) -> String {
+    let json =
+        serde_json::to_vec(payload).expect("payloads are always valid json");
+
+    hex::encode(Sha256::digest(json))
+}
+
+/// Turns an unsuccessful response from a sink's API into an error
+pub async fn check_response(sink: SinkKind, res: Response) -> Result<Response> {
+    if res.status() == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = res
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.parse().ok())
+            .unwrap_or(1);
+
+        return Err(ApplicationError::SinkRatelimited {
+            sink,
+            retry_after: Duration::from_secs(retry_after),
+        });
+    }
+
+    if let Err(err) = res.error_for_status_ref() {
+        let status = res.status();
+        let body = res.text().await.unwrap_or_default();
+
+        let error = if body.is_empty() {
+            err.to_string()
+        } else {
+            format!("{}: {}", status, body)
+        };
+
+        // server errors are usually temporary, so they're kept apart from
+        // requests that will never succeed
+        return Err(if status.is_server_error() {
+            ApplicationError::SinkServerError { sink, error }
+        } else {
+            ApplicationError::SinkApiError { sink, error }
+        });
+    }
+
+    Ok(res)
+}
@@ -0,0 +1,234 @@
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::{Client as ReqwestClient, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::{
+    check_response,
+    Destination,
+    Notification,
+    NotificationKind,
+    NotificationSink,
+};
+use crate::{
+    db::SinkKind,
+    error::{ApplicationError, Result},
+    push::PushMessage,
+    scheduler::Route,
+    statuspage::StatusIndicator,
+};
+
+const NTFY_DEFAULT_SERVER: &str = "https://ntfy.sh";
+
+/// `sink_config` of an `ntfy` subscription. Protected topics need either an
+/// access token or a username and password
+#[derive(Deserialize)]
+struct NtfyConfig {
+    #[serde(default = "default_ntfy_server")]
+    server_url: String,
+    topic: String,
+    token: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+fn default_ntfy_server() -> String {
+    NTFY_DEFAULT_SERVER.to_string()
+}
+
+/// `sink_config` of a `gotify` subscription
+#[derive(Deserialize)]
+struct GotifyConfig {
+    server_url: String,
+
+    /// An application's token, which can only create messages
+    token: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NtfyMessage {
+    pub id: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GotifyMessage {
+    pub id: u64,
+}
+
+/// Publishes notifications to an ntfy topic
+#[derive(Default)]
+pub struct NtfySink {
+    http: ReqwestClient,
+}
+
+/// Sends notifications to a Gotify application. Deleting them would need a
+/// client token, which subscriptions don't have
+#[derive(Default)]
+pub struct GotifySink {
+    http: ReqwestClient,
+}
+
+impl NotificationSink for NtfySink {
+    type MessageRef = NtfyMessage;
+    type Payload = PushMessage;
+
+    fn render(&self, notification: &Notification) -> PushMessage {
+        render(notification)
+    }
+
+    fn route(&self, destination: &Destination) -> Route {
+        let address = destination
+            .config::<NtfyConfig>()
+            .map(|c| format!("{}/{}", c.server_url, c.topic))
+            .unwrap_or_default();
+
+        Route::sink(SinkKind::Ntfy, &address)
+    }
+
+    fn can_edit(&self, _destination: &Destination) -> bool {
+        false
+    }
+
+    fn create<'a>(
+        &'a self,
+        destination: &'a Destination,
+        message: &'a PushMessage,
+    ) -> BoxFuture<'a, Result<NtfyMessage>> {
+        async move {
+            let config: NtfyConfig =
+                destination.config().map_err(|e| destination.invalid(e))?;
+
+            // publishing as JSON goes to the server's root, with the topic in
+            // the body
+            // https://docs.ntfy.sh/publish/#publish-as-json
+            let mut req = self.http.post(&config.server_url).json(&json!({
+                "topic": config.topic,
+                "title": message.title,
+                "message": message.message,
+                "priority": get_ntfy_priority(message.impact),
+                "tags": [message.impact.as_str()],
+                "click": message.click,
+            }));
+
+            if let Some(token) = &config.token {
+                req = req.bearer_auth(token);
+            } else if let Some(username) = &config.username {
+                req = req.basic_auth(username, config.password.as_ref());
+            }
+
+            send(SinkKind::Ntfy, req).await
+        }
+        .boxed()
+    }
+}
+
+impl NotificationSink for GotifySink {
+    type MessageRef = GotifyMessage;
+    type Payload = PushMessage;
+
+    fn render(&self, notification: &Notification) -> PushMessage {
+        render(notification)
+    }
+
+    fn route(&self, destination: &Destination) -> Route {
+        let url = destination
+            .config::<GotifyConfig>()
+            .map(|c| c.server_url)
+            .unwrap_or_default();
+
+        Route::sink(SinkKind::Gotify, &url)
+    }
+
+    fn can_edit(&self, _destination: &Destination) -> bool {
+        false
+    }
+
+    fn create<'a>(
+        &'a self,
+        destination: &'a Destination,
+        message: &'a PushMessage,
+    ) -> BoxFuture<'a, Result<GotifyMessage>> {
+        async move {
+            let config: GotifyConfig =
+                destination.config().map_err(|e| destination.invalid(e))?;
+
+            // https://gotify.net/docs/msgextras
+            let req = self
+                .http
+                .post(format!(
+                    "{}/message",
+                    config.server_url.trim_end_matches('/')
+                ))
+                .header("X-Gotify-Key", &config.token)
+                .json(&json!({
+                    "title": message.title,
+                    "message": message.message,
+                    "priority": get_gotify_priority(message.impact),
+                    "extras": {
+                        "client::notification": {
+                            "click": { "url": message.click },
+                        },
+                    },
+                }));
+
+            send(SinkKind::Gotify, req).await
+        }
+        .boxed()
+    }
+}
+
+fn render(notification: &Notification) -> PushMessage {
+    let Notification {
+        incident, branding, ..
+    } = notification;
+
+    match notification.kind {
+        NotificationKind::Post(update) => {
+            PushMessage::post(incident, update, branding)
+        },
+        NotificationKind::Edit => PushMessage::edit(incident, branding),
+        NotificationKind::Postmortem(update) => {
+            PushMessage::postmortem(incident, update, branding)
+        },
+    }
+}
+
+/// Sends a request and reads the created message's id. Both services respond
+/// with the message, including its `id`
+async fn send<T>(sink: SinkKind, req: RequestBuilder) -> Result<T>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let res = req.send().await.map_err(|source| {
+        ApplicationError::SinkRequestError { sink, source }
+    })?;
+
+    check_response(sink, res)
+        .await?
+        .json()
+        .await
+        .map_err(|source| ApplicationError::SinkRequestError { sink, source })
+}
+
+/// ntfy's priorities go from 1 (min) to 5 (max), where only 4 and up make a
+/// sound by default
+/// https://docs.ntfy.sh/publish/#message-priority
+fn get_ntfy_priority(impact: StatusIndicator) -> u8 {
+    match impact {
+        StatusIndicator::None | StatusIndicator::Maintenance => 2,
+        StatusIndicator::Minor => 3,
+        StatusIndicator::Major => 4,
+        StatusIndicator::Critical => 5,
+    }
+}
+
+/// Gotify's priorities go from 0 to 10. The Android app is silent below 4
+/// and shows a heads-up notification from 8
+fn get_gotify_priority(impact: StatusIndicator) -> u8 {
+    match impact {
+        StatusIndicator::None | StatusIndicator::Maintenance => 2,
+        StatusIndicator::Minor => 5,
+        StatusIndicator::Major => 7,
+        StatusIndicator::Critical => 9,
+    }
+}
@@ -0,0 +1,368 @@
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::Client as ReqwestClient;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::types::JsonValue;
+
+use super::{
+    check_response,
+    Destination,
+    Notification,
+    NotificationKind,
+    NotificationSink,
+    WebhookMessage,
+};
+use crate::{
+    db::SinkKind,
+    error::{ApplicationError, Result},
+    scheduler::Route,
+    slack::SlackMessage,
+};
+
+/// `sink_config` of a `slack_webhook` subscription
+#[derive(Deserialize)]
+struct WebhookConfig {
+    webhook_url: String,
+}
+
+/// `sink_config` of a `slack_bot` subscription
+#[derive(Deserialize)]
+struct BotConfig {
+    /// ID or name of the channel to post in
+    channel: String,
+}
+
+/// A message posted by the bot. `ts` is Slack's ID for a message, which is
+/// only unique within its channel
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BotMessage {
+    pub channel: String,
+    pub ts: String,
+}
+
+/// Posts to a Slack incoming webhook
+#[derive(Default)]
+pub struct SlackWebhookSink {
+    http: ReqwestClient,
+}
+
+impl NotificationSink for SlackWebhookSink {
+    type MessageRef = WebhookMessage;
+    type Payload = SlackMessage;
+
+    fn render(&self, notification: &Notification) -> SlackMessage {
+        render(notification)
+    }
+
+    fn route(&self, destination: &Destination) -> Route {
+        let url = destination
+            .config::<WebhookConfig>()
+            .map(|c| c.webhook_url)
+            .unwrap_or_default();
+
+        Route::sink(SinkKind::SlackWebhook, &url)
+    }
+
+    fn can_edit(&self, _destination: &Destination) -> bool {
+        false
+    }
+
+    fn create<'a>(
+        &'a self,
+        destination: &'a Destination,
+        message: &'a SlackMessage,
+    ) -> BoxFuture<'a, Result<WebhookMessage>> {
+        async move {
+            let config: WebhookConfig =
+                destination.config().map_err(|e| destination.invalid(e))?;
+
+            let res = self
+                .http
+                .post(&config.webhook_url)
+                .json(message)
+                .send()
+                .await
+                .map_err(|source| ApplicationError::SinkRequestError {
+                    sink: SinkKind::SlackWebhook,
+                    source,
+                })?;
+            check_response(SinkKind::SlackWebhook, res).await?;
+
+            Ok(WebhookMessage {})
+        }
+        .boxed()
+    }
+}
+
+/// Posts with a bot token through the Web API, which lets messages be
+/// edited with `chat.update`
+pub struct SlackBotSink {
+    http: ReqwestClient,
+    token: String,
+
+    /// Where the Web API is, without a trailing slash
+    api_url: String,
+}
+
+impl SlackBotSink {
+    pub fn new(token: String, api_url: &str) -> Self {
+        Self {
+            http: ReqwestClient::new(),
+            token,
+            api_url: api_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Calls a Web API method, returning the response if it was `ok`
+    async fn call(&self, method: &str, body: JsonValue) -> Result<JsonValue> {
+        let sink = SinkKind::SlackBot;
+
+        let res = self
+            .http
+            .post(format!("{}/{}", self.api_url, method))
+            .bearer_auth(&self.token)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|source| ApplicationError::SinkRequestError {
+                sink,
+                source,
+            })?;
+        let res: JsonValue =
+            check_response(sink, res).await?.json().await.map_err(
+                |source| ApplicationError::SinkRequestError { sink, source },
+            )?;
+
+        if res["ok"].as_bool() == Some(true) {
+            return Ok(res);
+        }
+
+        // https://api.slack.com/methods/chat.update#errors
+        match res["error"].as_str().unwrap_or("unknown_error") {
+            "message_not_found" => {
+                Err(ApplicationError::MessageNotFound { sink })
+            },
+            error => Err(ApplicationError::SinkApiError {
+                sink,
+                error: error.to_string(),
+            }),
+        }
+    }
+}
+
+impl NotificationSink for SlackBotSink {
+    type MessageRef = BotMessage;
+    type Payload = SlackMessage;
+
+    fn render(&self, notification: &Notification) -> SlackMessage {
+        render(notification)
+    }
+
+    fn route(&self, destination: &Destination) -> Route {
+        let channel = destination
+            .config::<BotConfig>()
+            .map(|c| c.channel)
+            .unwrap_or_default();
+
+        Route::sink(SinkKind::SlackBot, &channel)
+    }
+
+    fn create<'a>(
+        &'a self,
+        destination: &'a Destination,
+        message: &'a SlackMessage,
+    ) -> BoxFuture<'a, Result<BotMessage>> {
+        async move {
+            let config: BotConfig =
+                destination.config().map_err(|e| destination.invalid(e))?;
+
+            let res = self
+                .call(
+                    "chat.postMessage",
+                    json!({
+                        "channel": config.channel,
+                        "text": message.text,
+                        "attachments": message.attachments,
+                        "unfurl_links": false,
+                    }),
+                )
+                .await?;
+
+            // the response has the channel's ID even if it was posted to by
+            // name, and updates need the ID
+            match (res["channel"].as_str(), res["ts"].as_str()) {
+                (Some(channel), Some(ts)) => Ok(BotMessage {
+                    channel: channel.to_string(),
+                    ts: ts.to_string(),
+                }),
+                _ => Err(ApplicationError::SinkApiError {
+                    sink: SinkKind::SlackBot,
+                    error: "response is missing the message".to_string(),
+                }),
+            }
+        }
+        .boxed()
+    }
+
+    fn edit<'a>(
+        &'a self,
+        _destination: &'a Destination,
+        message: &'a BotMessage,
+        payload: &'a SlackMessage,
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            self.call(
+                "chat.update",
+                json!({
+                    "channel": message.channel,
+                    "ts": message.ts,
+                    "text": payload.text,
+                    "attachments": payload.attachments,
+                }),
+            )
+            .await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn delete<'a>(
+        &'a self,
+        _destination: &'a Destination,
+        message: &'a BotMessage,
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            self.call(
+                "chat.delete",
+                json!({
+                    "channel": message.channel,
+                    "ts": message.ts,
+                }),
+            )
+            .await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// Slack doesn't have role pings or a plain text format, so only the kind of
+/// notification matters
+fn render(notification: &Notification) -> SlackMessage {
+    let Notification {
+        incident, branding, ..
+    } = notification;
+
+    match notification.kind {
+        NotificationKind::Post(update) => {
+            SlackMessage::post(incident, update, branding)
+        },
+        NotificationKind::Edit => SlackMessage::edit(incident, branding),
+        NotificationKind::Postmortem(update) => {
+            SlackMessage::postmortem(incident, update, branding)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::http::Method;
+    use reqwest::header::{HeaderMap, RETRY_AFTER};
+
+    use super::*;
+    use crate::{
+        branding::Branding,
+        sinks::testing::{destination, incident, TestServer},
+    };
+
+    fn message() -> SlackMessage {
+        SlackMessage::edit(&incident(), &Branding::default())
+    }
+
+    #[tokio::test]
+    async fn bot_posts_and_edits() {
+        let server = TestServer::start();
+        let sink = SlackBotSink::new("xoxb-token".to_string(), &server.url);
+        let destination =
+            destination(SinkKind::SlackBot, json!({ "channel": "#status" }));
+
+        server.respond(
+            200,
+            json!({ "ok": true, "channel": "C123", "ts": "1700000000.000100" }),
+        );
+        let sent = sink.create(&destination, &message()).await.unwrap();
+        assert_eq!(sent.channel, "C123");
+        assert_eq!(sent.ts, "1700000000.000100");
+
+        server.respond(200, json!({ "ok": true }));
+        sink.edit(&destination, &sent, &message()).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, Method::POST);
+        assert_eq!(requests[0].path, "/chat.postMessage");
+        assert_eq!(requests[0].headers["authorization"], "Bearer xoxb-token");
+        assert_eq!(requests[0].body["channel"], "#status");
+        assert_eq!(requests[0].body["text"], message().text);
+        assert_eq!(requests[1].path, "/chat.update");
+        assert_eq!(requests[1].body["channel"], "C123");
+        assert_eq!(requests[1].body["ts"], "1700000000.000100");
+    }
+
+    #[tokio::test]
+    async fn bot_errors() {
+        let server = TestServer::start();
+        let sink = SlackBotSink::new("xoxb-token".to_string(), &server.url);
+        let destination =
+            destination(SinkKind::SlackBot, json!({ "channel": "#status" }));
+        let sent = BotMessage {
+            channel: "C123".to_string(),
+            ts: "1700000000.000100".to_string(),
+        };
+
+        server
+            .respond(200, json!({ "ok": false, "error": "message_not_found" }));
+        let err = sink
+            .edit(&destination, &sent, &message())
+            .await
+            .unwrap_err();
+        assert!(err.is_unknown_message());
+
+        server
+            .respond(200, json!({ "ok": false, "error": "channel_not_found" }));
+        let err = sink.create(&destination, &message()).await.unwrap_err();
+        assert!(
+            matches!(err, ApplicationError::SinkApiError { error, .. } if error == "channel_not_found")
+        );
+
+        server.respond(503, JsonValue::Null);
+        let err = sink.create(&destination, &message()).await.unwrap_err();
+        assert!(matches!(err, ApplicationError::SinkServerError { .. }));
+    }
+
+    #[tokio::test]
+    async fn webhook_ratelimited() {
+        let server = TestServer::start();
+        let sink = SlackWebhookSink::default();
+        let destination = destination(
+            SinkKind::SlackWebhook,
+            json!({ "webhook_url": format!("{}/services/T0/B0/x", server.url) }),
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, "30".parse().unwrap());
+        server.respond_with_headers(429, headers, json!("rate_limited"));
+        let err = sink.create(&destination, &message()).await.unwrap_err();
+        assert_eq!(err.ratelimit(), Some((false, Duration::from_secs(30))));
+
+        server.respond(200, json!("ok"));
+        sink.create(&destination, &message()).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[1].path, "/services/T0/B0/x");
+        assert_eq!(requests[1].body["text"], message().text);
+    }
+}
@@ -0,0 +1,210 @@
+use std::time::Duration;
+
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::Client as ReqwestClient;
+use serde::Deserialize;
+
+use super::{
+    check_response,
+    Destination,
+    Notification,
+    NotificationKind,
+    NotificationSink,
+    WebhookMessage,
+};
+use crate::{
+    db::SinkKind,
+    error::{ApplicationError, Result},
+    scheduler::Route,
+    teams::TeamsMessage,
+};
+
+/// `sink_config` of a `teams` subscription
+#[derive(Deserialize)]
+struct TeamsConfig {
+    /// An incoming webhook, or a workflow's "When a Teams webhook request is
+    /// received" URL
+    webhook_url: String,
+}
+
+/// Posts Adaptive Cards to a Teams webhook
+#[derive(Default)]
+pub struct TeamsSink {
+    http: ReqwestClient,
+}
+
+impl NotificationSink for TeamsSink {
+    type MessageRef = WebhookMessage;
+    type Payload = TeamsMessage;
+
+    fn render(&self, notification: &Notification) -> TeamsMessage {
+        let Notification {
+            incident, branding, ..
+        } = notification;
+
+        match notification.kind {
+            NotificationKind::Post(update) => {
+                TeamsMessage::post(incident, update, branding)
+            },
+            NotificationKind::Edit => TeamsMessage::edit(incident, branding),
+            NotificationKind::Postmortem(update) => {
+                TeamsMessage::postmortem(incident, update, branding)
+            },
+        }
+    }
+
+    fn route(&self, destination: &Destination) -> Route {
+        let url = destination
+            .config::<TeamsConfig>()
+            .map(|c| c.webhook_url)
+            .unwrap_or_default();
+
+        Route::sink(SinkKind::Teams, &url)
+    }
+
+    fn can_edit(&self, _destination: &Destination) -> bool {
+        false
+    }
+
+    fn create<'a>(
+        &'a self,
+        destination: &'a Destination,
+        message: &'a TeamsMessage,
+    ) -> BoxFuture<'a, Result<WebhookMessage>> {
+        async move {
+            let config: TeamsConfig =
+                destination.config().map_err(|e| destination.invalid(e))?;
+
+            let res = self
+                .http
+                .post(&config.webhook_url)
+                .json(message)
+                .send()
+                .await
+                .map_err(|source| ApplicationError::SinkRequestError {
+                    sink: SinkKind::Teams,
+                    source,
+                })?;
+            let body = check_response(SinkKind::Teams, res)
+                .await?
+                .text()
+                .await
+                .unwrap_or_default();
+
+            // connectors respond with 200 even when delivery fails, with the
+            // error in the body
+            if body.starts_with("Webhook message delivery failed") {
+                return Err(if body.contains("429") {
+                    ApplicationError::SinkRatelimited {
+                        sink: SinkKind::Teams,
+                        retry_after: Duration::from_secs(1),
+                    }
+                } else {
+                    ApplicationError::SinkApiError {
+                        sink: SinkKind::Teams,
+                        error: body,
+                    }
+                });
+            }
+
+            Ok(WebhookMessage {})
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use sqlx::types::JsonValue;
+
+    use super::*;
+    use crate::{
+        branding::Branding,
+        sinks::testing::{destination, incident, TestServer},
+    };
+
+    fn message() -> TeamsMessage {
+        TeamsMessage::edit(&incident(), &Branding::default())
+    }
+
+    fn teams(server: &TestServer) -> Destination {
+        destination(
+            SinkKind::Teams,
+            json!({ "webhook_url": format!("{}/webhookb2/abc", server.url) }),
+        )
+    }
+
+    #[tokio::test]
+    async fn posts_cards() {
+        let server = TestServer::start();
+        let sink = TeamsSink::default();
+        let destination = teams(&server);
+
+        server.respond(200, json!("1"));
+        sink.create(&destination, &message()).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/webhookb2/abc");
+        assert_eq!(requests[0].body["type"], "message");
+
+        let card = &requests[0].body["attachments"][0];
+        assert_eq!(
+            card["contentType"],
+            "application/vnd.microsoft.card.adaptive"
+        );
+        assert_eq!(
+            card["content"]["body"][0]["items"][0]["text"],
+            "Voice Connection Failures"
+        );
+        assert_eq!(
+            card["content"]["actions"][0]["url"],
+            "https://stspg.io/x2tpl4"
+        );
+
+        let err = sink
+            .edit(&destination, &WebhookMessage {}, &message())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ApplicationError::Unsupported {
+                sink: SinkKind::Teams,
+                action: "edit"
+            }
+        ));
+    }
+
+    /// Connectors fail with a 200 and the error in the body
+    #[tokio::test]
+    async fn errors_in_successful_responses() {
+        let server = TestServer::start();
+        let sink = TeamsSink::default();
+        let destination = teams(&server);
+
+        server.respond(
+            200,
+            json!("Webhook message delivery failed with error: Microsoft Teams endpoint returned HTTP error 429 with ContextId tcid=0"),
+        );
+        let err = sink.create(&destination, &message()).await.unwrap_err();
+        assert!(err.ratelimit().is_some());
+
+        server.respond(
+            200,
+            json!("Webhook message delivery failed with error: Microsoft Teams endpoint returned HTTP error 413 with ContextId tcid=0"),
+        );
+        let err = sink.create(&destination, &message()).await.unwrap_err();
+        assert!(matches!(err, ApplicationError::SinkApiError { .. }));
+
+        server.respond(429, JsonValue::Null);
+        let err = sink.create(&destination, &message()).await.unwrap_err();
+        assert!(err.ratelimit().is_some());
+
+        server.respond(
+            400,
+            json!("Bad payload received by generic incoming webhook."),
+        );
+        let err = sink.create(&destination, &message()).await.unwrap_err();
+        assert!(matches!(err, ApplicationError::SinkApiError { .. }));
+    }
+}
@@ -0,0 +1,397 @@
+use std::time::Duration;
+
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::Client as ReqwestClient;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::types::JsonValue;
+
+use super::{Destination, Notification, NotificationKind, NotificationSink};
+use crate::{
+    db::SinkKind,
+    error::{ApplicationError, Result},
+    scheduler::Route,
+    telegram::TelegramMessage,
+};
+
+/// `sink_config` of a `telegram` subscription
+#[derive(Deserialize)]
+struct TelegramConfig {
+    chat_id: ChatId,
+
+    /// The topic to post in, in groups with topics turned on
+    message_thread_id: Option<i64>,
+}
+
+/// A chat's numeric ID, or a public channel's `@username`
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum ChatId {
+    Id(i64),
+    Username(String),
+}
+
+impl ChatId {
+    fn to_address(&self) -> String {
+        match self {
+            Self::Id(id) => id.to_string(),
+            Self::Username(username) => username.clone(),
+        }
+    }
+}
+
+/// Always has the chat's numeric ID, even if it was posted to by username
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SentMessage {
+    pub chat_id: i64,
+    pub message_id: i64,
+}
+
+/// Every Bot API method responds with this, whether or not it succeeded
+/// https://core.telegram.org/bots/api#making-requests
+#[derive(Deserialize)]
+struct ApiResponse {
+    ok: bool,
+    #[serde(default)]
+    result: JsonValue,
+    error_code: Option<u16>,
+    description: Option<String>,
+    parameters: Option<ResponseParameters>,
+}
+
+#[derive(Deserialize)]
+struct ResponseParameters {
+    retry_after: Option<u64>,
+}
+
+/// Sends HTML messages as a Telegram bot, and edits them in edit mode
+pub struct TelegramSink {
+    http: ReqwestClient,
+    token: String,
+
+    /// Where the Bot API is, without a trailing slash
+    api_url: String,
+}
+
+impl TelegramSink {
+    pub fn new(token: String, api_url: &str) -> Self {
+        Self {
+            http: ReqwestClient::new(),
+            token,
+            api_url: api_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Calls a Bot API method, returning its result if it was `ok`
+    async fn call(
+        &self,
+        method: &str,
+        mut body: JsonValue,
+    ) -> Result<JsonValue> {
+        let sink = SinkKind::Telegram;
+
+        // optional parameters are left out instead of being sent as null
+        if let Some(params) = body.as_object_mut() {
+            params.retain(|_, v| !v.is_null());
+        }
+
+        // the token is part of the URL, so it's left out of errors
+        let res = self
+            .http
+            .post(format!("{}/bot{}/{}", self.api_url, self.token, method))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|source| ApplicationError::SinkRequestError {
+                sink,
+                source: source.without_url(),
+            })?;
+
+        let status = res.status();
+        let res: ApiResponse = match res.json().await {
+            Ok(res) => res,
+            Err(_) if status.is_server_error() => {
+                return Err(ApplicationError::SinkServerError {
+                    sink,
+                    error: status.to_string(),
+                })
+            },
+            Err(source) => {
+                return Err(ApplicationError::SinkRequestError {
+                    sink,
+                    source: source.without_url(),
+                })
+            },
+        };
+
+        if res.ok {
+            return Ok(res.result);
+        }
+
+        let code = res.error_code.unwrap_or(status.as_u16());
+        let description = res.description.unwrap_or_default();
+
+        Err(match code {
+            429 => ApplicationError::SinkRatelimited {
+                sink,
+                retry_after: Duration::from_secs(
+                    res.parameters.and_then(|p| p.retry_after).unwrap_or(1),
+                ),
+            },
+            500.. => ApplicationError::SinkServerError {
+                sink,
+                error: format!("{}: {}", code, description),
+            },
+            _ if description.contains("message to edit not found")
+                || description.contains("message to delete not found") =>
+            {
+                ApplicationError::MessageNotFound { sink }
+            },
+            _ => ApplicationError::SinkApiError {
+                sink,
+                error: format!("{}: {}", code, description),
+            },
+        })
+    }
+}
+
+impl NotificationSink for TelegramSink {
+    type MessageRef = SentMessage;
+    type Payload = TelegramMessage;
+
+    fn render(&self, notification: &Notification) -> TelegramMessage {
+        let Notification {
+            incident, branding, ..
+        } = notification;
+
+        match notification.kind {
+            NotificationKind::Post(update) => {
+                TelegramMessage::post(incident, update, branding)
+            },
+            NotificationKind::Edit => TelegramMessage::edit(incident, branding),
+            NotificationKind::Postmortem(update) => {
+                TelegramMessage::postmortem(incident, update, branding)
+            },
+        }
+    }
+
+    fn route(&self, destination: &Destination) -> Route {
+        let chat = destination
+            .config::<TelegramConfig>()
+            .map(|c| c.chat_id.to_address())
+            .unwrap_or_default();
+
+        Route::sink(SinkKind::Telegram, &chat)
+    }
+
+    fn create<'a>(
+        &'a self,
+        destination: &'a Destination,
+        message: &'a TelegramMessage,
+    ) -> BoxFuture<'a, Result<SentMessage>> {
+        async move {
+            let config: TelegramConfig =
+                destination.config().map_err(|e| destination.invalid(e))?;
+
+            let res = self
+                .call(
+                    "sendMessage",
+                    json!({
+                        "chat_id": config.chat_id,
+                        "message_thread_id": config.message_thread_id,
+                        "text": message.text,
+                        "parse_mode": "HTML",
+                        "link_preview_options": { "is_disabled": true },
+                        "reply_markup": make_reply_markup(message),
+                    }),
+                )
+                .await?;
+
+            match (res["chat"]["id"].as_i64(), res["message_id"].as_i64()) {
+                (Some(chat_id), Some(message_id)) => Ok(SentMessage {
+                    chat_id,
+                    message_id,
+                }),
+                _ => Err(ApplicationError::SinkApiError {
+                    sink: SinkKind::Telegram,
+                    error: "response is missing the message".to_string(),
+                }),
+            }
+        }
+        .boxed()
+    }
+
+    fn edit<'a>(
+        &'a self,
+        _destination: &'a Destination,
+        message: &'a SentMessage,
+        payload: &'a TelegramMessage,
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            let res = self
+                .call(
+                    "editMessageText",
+                    json!({
+                        "chat_id": message.chat_id,
+                        "message_id": message.message_id,
+                        "text": payload.text,
+                        "parse_mode": "HTML",
+                        "link_preview_options": { "is_disabled": true },
+                        "reply_markup": make_reply_markup(payload),
+                    }),
+                )
+                .await;
+
+            // edits that wouldn't change anything fail, but the message is
+            // already what it should be
+            match res {
+                Err(ApplicationError::SinkApiError { error, .. })
+                    if error.contains("message is not modified") =>
+                {
+                    Ok(())
+                },
+                res => res.map(|_| ()),
+            }
+        }
+        .boxed()
+    }
+
+    fn delete<'a>(
+        &'a self,
+        _destination: &'a Destination,
+        message: &'a SentMessage,
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            self.call(
+                "deleteMessage",
+                json!({
+                    "chat_id": message.chat_id,
+                    "message_id": message.message_id,
+                }),
+            )
+            .await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// The buttons in one row, like Discord's link buttons. Leaving the markup
+/// out of an edit removes the buttons
+/// https://core.telegram.org/bots/api#inlinekeyboardmarkup
+fn make_reply_markup(message: &TelegramMessage) -> JsonValue {
+    if message.buttons.is_empty() {
+        return JsonValue::Null;
+    }
+
+    json!({ "inline_keyboard": [message.buttons] })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        branding::Branding,
+        sinks::testing::{destination, incident, TestServer},
+    };
+
+    fn message() -> TelegramMessage {
+        TelegramMessage::edit(&incident(), &Branding::default())
+    }
+
+    fn sent() -> SentMessage {
+        SentMessage {
+            chat_id: -1001234567890,
+            message_id: 42,
+        }
+    }
+
+    fn error(code: u16, description: &str) -> JsonValue {
+        json!({ "ok": false, "error_code": code, "description": description })
+    }
+
+    #[tokio::test]
+    async fn posts_and_edits() {
+        let server = TestServer::start();
+        let sink = TelegramSink::new("123:abc".to_string(), &server.url);
+        let destination =
+            destination(SinkKind::Telegram, json!({ "chat_id": "@status" }));
+
+        server.respond(
+            200,
+            json!({
+                "ok": true,
+                "result": { "message_id": 42, "chat": { "id": -1001234567890i64 } },
+            }),
+        );
+        let sent = sink.create(&destination, &message()).await.unwrap();
+        assert_eq!(sent.chat_id, -1001234567890);
+        assert_eq!(sent.message_id, 42);
+
+        server.respond(200, json!({ "ok": true, "result": true }));
+        sink.edit(&destination, &sent, &message()).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/bot123:abc/sendMessage");
+        assert_eq!(requests[0].body["chat_id"], "@status");
+        assert_eq!(requests[0].body["text"], message().text);
+        assert_eq!(requests[0].body["parse_mode"], "HTML");
+        assert_eq!(
+            requests[0].body["reply_markup"]["inline_keyboard"][0],
+            json!([
+                { "text": "View on status page", "url": "https://stspg.io/x2tpl4" },
+                { "text": "Postmortem", "url": "https://stspg.io/x2tpl4" },
+            ])
+        );
+        assert!(requests[0].body.get("message_thread_id").is_none());
+
+        assert_eq!(requests[1].path, "/bot123:abc/editMessageText");
+        assert_eq!(requests[1].body["chat_id"], -1001234567890i64);
+        assert_eq!(requests[1].body["message_id"], 42);
+    }
+
+    #[tokio::test]
+    async fn errors() {
+        let server = TestServer::start();
+        let sink = TelegramSink::new("123:abc".to_string(), &server.url);
+        let destination =
+            destination(SinkKind::Telegram, json!({ "chat_id": "@status" }));
+
+        server.respond(
+            429,
+            json!({
+                "ok": false,
+                "error_code": 429,
+                "description": "Too Many Requests: retry after 17",
+                "parameters": { "retry_after": 17 },
+            }),
+        );
+        let err = sink.create(&destination, &message()).await.unwrap_err();
+        assert_eq!(err.ratelimit(), Some((false, Duration::from_secs(17))));
+
+        server
+            .respond(400, error(400, "Bad Request: message to edit not found"));
+        let err = sink.edit(&destination, &sent(), &message()).await;
+        assert!(err.unwrap_err().is_unknown_message());
+
+        server.respond(
+            400,
+            error(
+                400,
+                "Bad Request: message is not modified: specified new message content and reply markup are exactly the same as a current content and reply markup of the message",
+            ),
+        );
+        sink.edit(&destination, &sent(), &message()).await.unwrap();
+
+        server.respond(400, error(400, "Bad Request: chat not found"));
+        let err = sink.create(&destination, &message()).await.unwrap_err();
+        assert!(matches!(err, ApplicationError::SinkApiError { .. }));
+
+        // the token is in the URL, so it can't end up in logs
+        server.respond(502, json!("<html>Bad Gateway</html>"));
+        let err = sink.create(&destination, &message()).await.unwrap_err();
+        assert!(matches!(err, ApplicationError::SinkServerError { .. }));
+        assert!(!err.to_string().contains("123:abc"));
+    }
+}
@@ -0,0 +1,137 @@
+//! A stand-in for the APIs sinks talk to, so they can be tested without a
+//! network
+
+use std::{
+    collections::VecDeque,
+    net::{SocketAddr, TcpListener},
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header::CONTENT_TYPE, HeaderMap, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Router,
+};
+use sqlx::types::JsonValue;
+
+use super::Destination;
+use crate::{db::SinkKind, replay::Snapshot, statuspage::Incident};
+
+/// The recording the poller is tested against
+const RECORDING: &str = include_str!("../../tests/fixtures/incidents.jsonl");
+
+/// A request the server got, with its body parsed as JSON if it was JSON
+#[derive(Debug)]
+pub struct Request {
+    pub method: Method,
+    pub path: String,
+    pub headers: HeaderMap,
+    pub body: JsonValue,
+}
+
+#[derive(Default)]
+struct Shared {
+    requests: Vec<Request>,
+    responses: VecDeque<(StatusCode, HeaderMap, JsonValue)>,
+}
+
+/// Records every request and answers it with the next queued response, or
+/// an empty JSON object once there are none left
+pub struct TestServer {
+    pub url: String,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl TestServer {
+    pub fn start() -> Self {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let listener =
+            TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let app = Router::new().fallback(handle).with_state(shared.clone());
+        let server = axum::Server::from_tcp(listener)
+            .unwrap()
+            .serve(app.into_make_service());
+        tokio::spawn(server);
+
+        Self { url, shared }
+    }
+
+    pub fn respond(&self, status: u16, body: JsonValue) {
+        self.respond_with_headers(status, HeaderMap::new(), body);
+    }
+
+    pub fn respond_with_headers(
+        &self,
+        status: u16,
+        headers: HeaderMap,
+        body: JsonValue,
+    ) {
+        self.shared.lock().unwrap().responses.push_back((
+            StatusCode::from_u16(status).unwrap(),
+            headers,
+            body,
+        ));
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        std::mem::take(&mut self.shared.lock().unwrap().requests)
+    }
+}
+
+async fn handle(
+    State(shared): State<Arc<Mutex<Shared>>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let mut shared = shared.lock().unwrap();
+
+    shared.requests.push(Request {
+        method,
+        path: uri.to_string(),
+        headers,
+        body: serde_json::from_slice(&body).unwrap_or_default(),
+    });
+
+    let (status, mut headers, body) = shared.responses.pop_front().unwrap_or((
+        StatusCode::OK,
+        HeaderMap::new(),
+        JsonValue::default(),
+    ));
+
+    // null stands for an empty body, and strings are sent as they are, for
+    // APIs that don't respond with JSON
+    let body = match body {
+        JsonValue::Null => String::new(),
+        JsonValue::String(text) => text,
+        body => {
+            headers.insert(CONTENT_TYPE, "application/json".parse().unwrap());
+            body.to_string()
+        },
+    };
+
+    (status, headers, body).into_response()
+}
+
+/// The recorded incident, once it was resolved and got a postmortem
+pub fn incident() -> Incident {
+    let snapshot: Snapshot =
+        serde_json::from_str(RECORDING.lines().last().unwrap()).unwrap();
+
+    snapshot.incidents.incidents.into_iter().next().unwrap()
+}
+
+pub fn destination(sink: SinkKind, config: JsonValue) -> Destination {
+    Destination {
+        sink,
+        channel_id: None,
+        webhook_id: None,
+        webhook_token: None,
+        config: Some(config),
+    }
+}
@@ -0,0 +1,221 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use futures::{future::BoxFuture, FutureExt};
+use hmac::{Hmac, Mac};
+use reqwest::Client as ReqwestClient;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tracing::warn;
+
+use super::{
+    check_response,
+    hash_json,
+    Destination,
+    Notification,
+    NotificationSink,
+};
+use crate::{
+    db::SinkKind,
+    error::{ApplicationError, Result},
+    scheduler::Route,
+    webhook::{EventKind, WebhookEvent},
+};
+
+const USER_AGENT: &str = "discord-status-webhook";
+
+/// How many times a delivery is attempted before giving up. Every attempt
+/// has the same delivery ID, so receivers can drop duplicates
+const MAX_ATTEMPTS: u32 = 4;
+
+/// Doubled after each failed attempt, so the last attempt is about 7 seconds
+/// after the first, well within the shutdown timeout
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `sink_config` of a `webhook` subscription
+#[derive(Deserialize)]
+struct WebhookConfig {
+    url: String,
+
+    /// Key for the `X-Status-Signature` HMAC
+    secret: String,
+}
+
+/// The first delivery sent for an incident. Later events are separate
+/// deliveries, but don't replace it
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Delivery {
+    pub id: String,
+}
+
+/// POSTs every change as a signed [`WebhookEvent`]. There's no message to
+/// edit, so an edit is another delivery with the new event
+#[derive(Default)]
+pub struct WebhookSink {
+    http: ReqwestClient,
+}
+
+impl WebhookSink {
+    /// Sends an event, retrying on connection errors, server errors and rate
+    /// limits
+    async fn deliver(
+        &self,
+        destination: &Destination,
+        event: &WebhookEvent,
+    ) -> Result<Delivery> {
+        let config: WebhookConfig =
+            destination.config().map_err(|e| destination.invalid(e))?;
+
+        let delivery_id = delivery_id(&config.url, event);
+        let body = serde_json::to_vec(event)
+            .expect("webhook events are always valid json");
+
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempt = 1;
+
+        loop {
+            let err =
+                match self.send(&config, &delivery_id, attempt, &body).await {
+                    Ok(_) => return Ok(Delivery { id: delivery_id }),
+                    Err(err) => err,
+                };
+
+            let wait = match retry_after(&err) {
+                Some(retry_after) if attempt < MAX_ATTEMPTS => {
+                    retry_after.max(backoff)
+                },
+                _ => return Err(err),
+            };
+
+            warn!(
+                delivery_id,
+                attempt,
+                retry_in = wait.as_secs_f64(),
+                "Webhook delivery failed: {}",
+                err,
+            );
+            tokio::time::sleep(wait).await;
+
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+
+    async fn send(
+        &self,
+        config: &WebhookConfig,
+        delivery_id: &str,
+        attempt: u32,
+        body: &[u8],
+    ) -> Result<()> {
+        let timestamp = Utc::now().timestamp().to_string();
+
+        let res = self
+            .http
+            .post(&config.url)
+            .timeout(REQUEST_TIMEOUT)
+            .header("Content-Type", "application/json")
+            .header("User-Agent", USER_AGENT)
+            .header("X-Status-Delivery", delivery_id)
+            .header("X-Status-Attempt", attempt)
+            .header("X-Status-Timestamp", &timestamp)
+            .header(
+                "X-Status-Signature",
+                sign(&config.secret, &timestamp, body),
+            )
+            .body(body.to_vec())
+            .send()
+            .await
+            .map_err(|source| ApplicationError::SinkRequestError {
+                sink: SinkKind::Webhook,
+                source,
+            })?;
+        check_response(SinkKind::Webhook, res).await?;
+
+        Ok(())
+    }
+}
+
+impl NotificationSink for WebhookSink {
+    type MessageRef = Delivery;
+    type Payload = WebhookEvent;
+
+    fn render(&self, notification: &Notification) -> WebhookEvent {
+        WebhookEvent::new(notification.incident, notification.update)
+    }
+
+    fn route(&self, destination: &Destination) -> Route {
+        let url = destination
+            .config::<WebhookConfig>()
+            .map(|c| c.url)
+            .unwrap_or_default();
+
+        Route::sink(SinkKind::Webhook, &url)
+    }
+
+    /// Every edit is another delivery, and one without a change to describe
+    /// renders differently from the one that was sent
+    fn can_repair(&self, _destination: &Destination) -> bool {
+        false
+    }
+
+    fn create<'a>(
+        &'a self,
+        destination: &'a Destination,
+        event: &'a WebhookEvent,
+    ) -> BoxFuture<'a, Result<Delivery>> {
+        self.deliver(destination, event).boxed()
+    }
+
+    fn edit<'a>(
+        &'a self,
+        destination: &'a Destination,
+        _message: &'a Delivery,
+        event: &'a WebhookEvent,
+    ) -> BoxFuture<'a, Result<()>> {
+        async move {
+            // there's no change to describe, and failed deliveries are
+            // already retried
+            if event.event == EventKind::IncidentUpdated {
+                return Ok(());
+            }
+
+            self.deliver(destination, event).await?;
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+/// The same for every attempt at delivering an event to a URL, including
+/// after a restart
+fn delivery_id(url: &str, event: &WebhookEvent) -> String {
+    hash_json(&(url, event))[..32].to_string()
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `{timestamp}.{body}`. Including the
+/// timestamp lets receivers reject replayed deliveries
+fn sign(secret: &str, timestamp: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("hmac accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// How long to wait before retrying, if the error is worth retrying.
+/// Anything else, like a 4xx response, will fail the same way again
+fn retry_after(err: &ApplicationError) -> Option<Duration> {
+    match err {
+        ApplicationError::SinkRatelimited { retry_after, .. } => {
+            Some(*retry_after)
+        },
+        ApplicationError::SinkRequestError { .. } => Some(Duration::ZERO),
+        ApplicationError::SinkServerError { .. } => Some(Duration::ZERO),
+        _ => None,
+    }
+}
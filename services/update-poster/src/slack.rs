@@ -0,0 +1,215 @@
+use serde::Serialize;
+
+use crate::{
+    branding::Branding,
+    embeds::POSTMORTEM_EXCERPT_MAX_LEN,
+    statuspage::{Incident, IncidentStatus, IncidentUpdate},
+    text::{get_update_heading, TextStyle},
+    util::{
+        get_embed_color,
+        get_excerpt,
+        get_links,
+        get_timeline_updates,
+        truncate_with_ellipsis,
+    },
+};
+
+/// https://api.slack.com/reference/block-kit/blocks
+const HEADER_MAX_LEN: usize = 150;
+const SECTION_MAX_LEN: usize = 3000;
+
+/// Leaves room for the header, author, footer and buttons in the 50 blocks
+/// a message can have
+const MAX_UPDATES: usize = 25;
+
+/// A message rendered with Block Kit. The blocks are wrapped in an
+/// attachment, which is the only way to get a colour bar next to them
+#[derive(Clone, Debug, Serialize)]
+pub struct SlackMessage {
+    /// Shown in notifications and by clients that can't render blocks
+    pub text: String,
+    pub attachments: Vec<Attachment>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Attachment {
+    pub color: String,
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    Header { text: Text },
+    Section { text: Text },
+    Context { elements: Vec<Element> },
+    Actions { elements: Vec<Element> },
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Text {
+    PlainText { text: String },
+    Mrkdwn { text: String },
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Element {
+    Mrkdwn { text: String },
+    Image { image_url: String, alt_text: String },
+    Button { text: Text, url: String },
+}
+
+impl SlackMessage {
+    pub fn post(
+        incident: &Incident,
+        update: &IncidentUpdate,
+        branding: &Branding,
+    ) -> Self {
+        let blocks = vec![make_update_section(update, branding)];
+
+        Self::build(incident, branding, &update.status, blocks, "Started")
+    }
+
+    pub fn edit(incident: &Incident, branding: &Branding) -> Self {
+        let blocks = get_timeline_updates(incident)
+            .into_iter()
+            .take(MAX_UPDATES)
+            .rev()
+            .map(|upd| make_update_section(upd, branding))
+            .collect();
+
+        Self::build(incident, branding, &incident.status, blocks, "Started")
+    }
+
+    pub fn postmortem(
+        incident: &Incident,
+        update: &IncidentUpdate,
+        branding: &Branding,
+    ) -> Self {
+        let text = format!(
+            "{}\n{}\n\n<{}|Read the full postmortem>",
+            get_update_heading(update, branding, TextStyle::Slack),
+            escape(&get_excerpt(&update.body, POSTMORTEM_EXCERPT_MAX_LEN)),
+            incident.shortlink,
+        );
+        let blocks = vec![Block::Section {
+            text: Text::Mrkdwn {
+                text: truncate_with_ellipsis(text, SECTION_MAX_LEN),
+            },
+        }];
+
+        Self::build(
+            incident,
+            branding,
+            &update.status,
+            blocks,
+            "Postmortem published",
+        )
+    }
+
+    fn build(
+        incident: &Incident,
+        branding: &Branding,
+        status: &IncidentStatus,
+        updates: Vec<Block>,
+        footer: &str,
+    ) -> Self {
+        let mut blocks = vec![
+            Block::Header {
+                text: Text::PlainText {
+                    text: truncate_with_ellipsis(
+                        incident.name.clone(),
+                        HEADER_MAX_LEN,
+                    ),
+                },
+            },
+            make_author(branding),
+        ];
+        blocks.extend(updates);
+        blocks.push(Block::Context {
+            elements: vec![Element::Mrkdwn {
+                text: format!(
+                    "{} {}",
+                    footer,
+                    TextStyle::Slack.timestamp(&incident.start_time())
+                ),
+            }],
+        });
+
+        let links = get_links(incident, branding);
+        if !links.is_empty() {
+            blocks.push(make_buttons(links));
+        }
+
+        Self {
+            text: format!("{}: {}", incident.name, status),
+            attachments: vec![Attachment {
+                color: format!(
+                    "#{:06X}",
+                    get_embed_color(branding, incident, status)
+                ),
+                blocks,
+            }],
+        }
+    }
+}
+
+fn make_author(branding: &Branding) -> Block {
+    let name = escape(&branding.author.name);
+    let mut elements = vec![];
+
+    if let Some(icon_url) = &branding.author.icon_url {
+        elements.push(Element::Image {
+            image_url: icon_url.clone(),
+            alt_text: branding.author.name.clone(),
+        });
+    }
+
+    elements.push(Element::Mrkdwn {
+        text: match &branding.author.url {
+            Some(url) => format!("*<{}|{}>*", url, name),
+            None => format!("*{}*", name),
+        },
+    });
+
+    Block::Context { elements }
+}
+
+fn make_update_section(update: &IncidentUpdate, branding: &Branding) -> Block {
+    let heading = get_update_heading(update, branding, TextStyle::Slack);
+    let body_len = SECTION_MAX_LEN.saturating_sub(heading.len() + 1);
+
+    Block::Section {
+        text: Text::Mrkdwn {
+            text: format!(
+                "{}\n{}",
+                heading,
+                truncate_with_ellipsis(escape(&update.body), body_len)
+            ),
+        },
+    }
+}
+
+fn make_buttons(links: Vec<(&str, String)>) -> Block {
+    let elements = links
+        .into_iter()
+        .map(|(label, url)| Element::Button {
+            text: Text::PlainText {
+                text: label.to_string(),
+            },
+            url,
+        })
+        .collect();
+
+    Block::Actions { elements }
+}
+
+/// Escapes the characters Slack uses for links and mentions
+/// https://api.slack.com/reference/surfaces/formatting#escaping
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
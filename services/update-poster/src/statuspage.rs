@@ -1,22 +1,35 @@
 use std::{
-    env,
+    collections::HashMap,
+    fmt,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
     time::Duration,
 };
 
 use chrono::{DateTime, Utc};
-use futures::Stream;
+use futures::{future::BoxFuture, FutureExt, Stream};
 use reqwest::Client as ReqwestClient;
 use serde::{Deserialize, Serialize};
 use tokio::{
     sync::{
         broadcast::Receiver,
-        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        mpsc::{self, Receiver as MpscReceiver, Sender},
     },
     time,
 };
 use tracing::{info, warn};
+
+use crate::{error::SourceError, metrics::Metrics};
+
+/// Somewhere incidents can be fetched from, like the status page itself or
+/// a recording of it
+pub trait IncidentSource: Send + Sync {
+    fn get_all_incidents(
+        &self,
+    ) -> BoxFuture<'_, Result<Incidents, SourceError>>;
+}
+
 #[derive(Clone)]
 pub struct StatuspageAPI {
     reqwest_client: ReqwestClient,
@@ -24,13 +37,12 @@ pub struct StatuspageAPI {
 }
 
 impl StatuspageAPI {
-    pub fn new() -> Self {
+    pub fn new(statuspage_api_url: String) -> Self {
         let reqwest_client = ReqwestClient::new();
 
         Self {
             reqwest_client,
-            statuspage_api_url: env::var("STATUSPAGE_URL")
-                .expect("Missing `STATUSPAGE_URL` in env"),
+            statuspage_api_url,
         }
     }
 
@@ -41,64 +53,138 @@ impl StatuspageAPI {
             .get(url)
             .send()
             .await?
+            .error_for_status()?
             .json::<Incidents>()
             .await
     }
+
+    /// The page's overall status and every component's current one
+    pub async fn get_summary(&self) -> reqwest::Result<Summary> {
+        let url = format!("{}/api/v2/summary.json", self.statuspage_api_url);
+
+        self.reqwest_client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Summary>()
+            .await
+    }
 }
 
-impl Default for StatuspageAPI {
-    fn default() -> Self {
-        Self::new()
+impl IncidentSource for StatuspageAPI {
+    fn get_all_incidents(
+        &self,
+    ) -> BoxFuture<'_, Result<Incidents, SourceError>> {
+        async move { Ok(StatuspageAPI::get_all_incidents(self).await?) }.boxed()
     }
 }
 
+/// Stream of changes to the status page. Every batch that was queued since
+/// the last one was taken is merged, so a slow consumer gets one update per
+/// incident with its latest state instead of falling further behind
 pub struct StatuspageUpdates {
-    rx: UnboundedReceiver<Vec<Update>>,
+    rx: MpscReceiver<Vec<Update>>,
 }
 
 impl StatuspageUpdates {
-    pub fn new(statuspage_api: StatuspageAPI) -> (Self, StatuspageUpdatesPoll) {
-        let (tx, rx) = mpsc::unbounded_channel();
-
-        (Self { rx }, StatuspageUpdatesPoll { tx, statuspage_api })
+    /// Creates the stream and its poller. Once `capacity` batches are
+    /// waiting, the poller stops until the consumer catches up
+    pub fn new(
+        source: Box<dyn IncidentSource>,
+        capacity: usize,
+        interval: Duration,
+        metrics: Arc<Metrics>,
+    ) -> (Self, StatuspageUpdatesPoll) {
+        let (tx, rx) = mpsc::channel(capacity);
+
+        (
+            Self { rx },
+            StatuspageUpdatesPoll::new(tx, source, interval, metrics),
+        )
     }
 }
 
 pub struct StatuspageUpdatesPoll {
-    tx: UnboundedSender<Vec<Update>>,
-    statuspage_api: StatuspageAPI,
+    tx: Sender<Vec<Update>>,
+    source: Box<dyn IncidentSource>,
+    interval: Duration,
+    metrics: Arc<Metrics>,
 }
 
 impl StatuspageUpdatesPoll {
     pub fn new(
-        tx: UnboundedSender<Vec<Update>>,
-        statuspage_api: StatuspageAPI,
+        tx: Sender<Vec<Update>>,
+        source: Box<dyn IncidentSource>,
+        interval: Duration,
+        metrics: Arc<Metrics>,
     ) -> Self {
-        Self { tx, statuspage_api }
+        Self {
+            tx,
+            source,
+            interval,
+            metrics,
+        }
     }
 
     pub async fn start(&self, mut stop: Receiver<()>) {
-        let mut prev = self.statuspage_api.get_all_incidents().await.unwrap();
+        // changes are found by comparing against this, so keep trying until
+        // the status page is reachable
+        let mut prev = loop {
+            match self.source.get_all_incidents().await {
+                Ok(prev) => break prev,
+                Err(SourceError::ReplayFinished) => {
+                    info!("No recorded incidents to replay");
+                    return;
+                },
+                Err(err) => {
+                    self.metrics.poll_failed();
+                    tracing::error!(
+                        "Failed to get initial incidents: {:#?}",
+                        err
+                    );
+                },
+            }
+
+            tokio::select! {
+                _ = time::sleep(self.interval) => {},
+                _ = stop.recv() => {
+                    info!("recvd stop signal");
+                    return;
+                }
+            };
+        };
+        self.metrics.poll_succeeded(&[]);
 
-        let mut interval = time::interval(Duration::from_secs(5));
+        let mut interval = time::interval(self.interval);
 
         loop {
-            let curr = match self.statuspage_api.get_all_incidents().await {
-                Ok(curr) => curr,
+            match self.source.get_all_incidents().await {
+                Ok(curr) => {
+                    let changes = Self::cmp_incidents(&prev, &curr);
+                    self.metrics.poll_succeeded(&changes);
+
+                    // waits while the queue is full, so the next poll is
+                    // compared against what was actually queued
+                    if !changes.is_empty()
+                        && self.tx.send(changes).await.is_err()
+                    {
+                        info!("Update stream closed");
+                        break;
+                    }
+
+                    prev = curr;
+                },
+                Err(SourceError::ReplayFinished) => {
+                    info!("Finished replaying recorded incidents");
+                    break;
+                },
                 Err(err) => {
+                    self.metrics.poll_failed();
                     warn!("Failed to get status page incidents: {:#?}", err);
-                    continue;
                 },
             };
 
-            let changes = self.cmp_incidents(&prev, &curr);
-
-            if !changes.is_empty() {
-                self.tx.send(changes).ok();
-            }
-
-            prev = curr;
-
             tokio::select! {
                 _ = interval.tick() => {},
                 _ = stop.recv() => {
@@ -109,8 +195,8 @@ impl StatuspageUpdatesPoll {
         }
     }
 
-    fn cmp_incidents(
-        &self,
+    /// Finds every change between two fetches of the status page
+    pub fn cmp_incidents(
         old_incidents: &Incidents,
         new_incidents: &Incidents,
     ) -> Vec<Update> {
@@ -119,15 +205,29 @@ impl StatuspageUpdatesPoll {
         for incident in &new_incidents.incidents {
             match old_incidents.incidents.iter().find(|i| i.id == incident.id) {
                 Some(i) => {
+                    let timestamps_changed = i.started_at
+                        != incident.started_at
+                        || i.monitoring_at != incident.monitoring_at
+                        || i.resolved_at != incident.resolved_at;
+
                     if i.status == incident.status
                         && i.updated_at == incident.updated_at
                         && i.incident_updates.len()
                             == incident.incident_updates.len()
+                        && !timestamps_changed
                     {
                         continue;
                     }
 
-                    for update in &incident.incident_updates {
+                    let prev_len = updated_incidents.len();
+
+                    // send new updates oldest first so post mode messages
+                    // show up in the same order as on the status page
+                    let mut updates =
+                        incident.incident_updates.iter().collect::<Vec<_>>();
+                    updates.sort_by_key(|u| u.display_time());
+
+                    for update in updates {
                         match i
                             .incident_updates
                             .iter()
@@ -137,6 +237,7 @@ impl StatuspageUpdatesPoll {
                                 if update.status != u.status
                                     || update.body != u.body
                                     || update.updated_at != u.updated_at
+                                    || update.display_at != u.display_at
                                 {
                                     info!(
                                         incident_id = &incident.id,
@@ -152,6 +253,23 @@ impl StatuspageUpdatesPoll {
                                 }
                             },
 
+                            None if update.status
+                                == IncidentStatus::Postmortem =>
+                            {
+                                info!(
+                                    incident_id = &incident.id,
+                                    update_id = &update.id,
+                                    "Incident postmortem published",
+                                );
+
+                                updated_incidents.push(
+                                    Update::PostmortemPublished(
+                                        incident.clone(),
+                                        update.clone(),
+                                    ),
+                                );
+                            },
+
                             None => {
                                 info!(
                                     incident_id = &incident.id,
@@ -166,10 +284,42 @@ impl StatuspageUpdatesPoll {
                             },
                         }
                     }
+
+                    // a backdated incident only changes the embed timestamp,
+                    // so re-render the messages for its latest update
+                    if timestamps_changed && updated_incidents.len() == prev_len
+                    {
+                        let latest = incident
+                            .incident_updates
+                            .iter()
+                            .max_by_key(|u| u.display_time())
+                            .and_then(|u| {
+                                i.incident_updates
+                                    .iter()
+                                    .find(|old| old.id == u.id)
+                                    .map(|old| (old, u))
+                            });
+
+                        if let Some((u_old, u_new)) = latest {
+                            info!(
+                                incident_id = &incident.id,
+                                "Incident timestamps were modified",
+                            );
+                            updated_incidents.push(Update::UpdateModified(
+                                incident.clone(),
+                                (u_old.clone(), u_new.clone()),
+                            ));
+                        }
+                    }
                 },
 
                 None => {
-                    if incident.created_at.signed_duration_since(Utc::now()).num_hours() > 1 {
+                    if incident
+                        .created_at
+                        .signed_duration_since(Utc::now())
+                        .num_hours()
+                        > 1
+                    {
                         info!(id = &incident.id, "Old incident?");
                         continue;
                     }
@@ -191,16 +341,139 @@ impl Stream for StatuspageUpdates {
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Option<Self::Item>> {
-        self.rx.poll_recv(cx)
+        let first = match self.rx.poll_recv(cx) {
+            Poll::Ready(Some(batch)) => batch,
+            other => return other,
+        };
+
+        let mut batches = vec![first];
+        while let Ok(batch) = self.rx.try_recv() {
+            batches.push(batch);
+        }
+
+        Poll::Ready(Some(coalesce_updates(batches)))
     }
 }
 
+/// Merges batches of updates in the order they were found. Every update gets
+/// the latest state of its incident, repeated changes to the same incident
+/// update collapse into one, and anything already covered by a new incident
+/// or incident update message is dropped. A new incident only keeps the
+/// incident updates it was found with, so the ones posted after it are still
+/// sent on their own to post mode subscriptions
+fn coalesce_updates(batches: Vec<Vec<Update>>) -> Vec<Update> {
+    if batches.len() == 1 {
+        return batches.into_iter().flatten().collect();
+    }
+
+    let mut latest = HashMap::new();
+    for update in batches.iter().flatten() {
+        let incident = update.incident();
+        latest.insert(incident.id.clone(), incident.clone());
+    }
+
+    let latest_update = |incident: &Incident, update: IncidentUpdate| {
+        incident
+            .incident_updates
+            .iter()
+            .find(|u| u.id == update.id)
+            .cloned()
+            .unwrap_or(update)
+    };
+
+    let mut coalesced: Vec<Update> = vec![];
+
+    for update in batches.into_iter().flatten() {
+        let incident = latest[&update.incident().id].clone();
+        let created = coalesced
+            .iter()
+            .any(|c| matches!(c, Update::Created(i) if i.id == incident.id));
+        let sent = |id: &str| {
+            coalesced.iter().any(|c| match c {
+                Update::UpdateCreated(_, u)
+                | Update::PostmortemPublished(_, u) => u.id == id,
+                _ => false,
+            })
+        };
+
+        match update {
+            Update::Created(found) => {
+                if created {
+                    continue;
+                }
+
+                let mut incident = incident;
+                let was_found = |u: &IncidentUpdate| {
+                    found.incident_updates.iter().any(|f| f.id == u.id)
+                };
+
+                // unless every update it was found with has been deleted since
+                if incident.incident_updates.iter().any(was_found) {
+                    incident.incident_updates.retain(was_found);
+                }
+
+                coalesced.push(Update::Created(incident));
+            },
+
+            Update::UpdateCreated(_, u) => {
+                if !sent(&u.id) {
+                    let u = latest_update(&incident, u);
+                    coalesced.push(Update::UpdateCreated(incident, u));
+                }
+            },
+
+            Update::PostmortemPublished(_, u) => {
+                if !sent(&u.id) {
+                    let u = latest_update(&incident, u);
+                    coalesced.push(Update::PostmortemPublished(incident, u));
+                }
+            },
+
+            Update::UpdateModified(..) if created => {},
+
+            Update::UpdateModified(_, (u_old, u_new)) => {
+                if sent(&u_new.id) {
+                    continue;
+                }
+
+                let u_new = latest_update(&incident, u_new);
+                let prev = coalesced.iter_mut().find(|c| {
+                    matches!(c, Update::UpdateModified(_, (_, u)) if u.id == u_new.id)
+                });
+
+                match prev {
+                    Some(Update::UpdateModified(i, (_, u))) => {
+                        *i = incident;
+                        *u = u_new;
+                    },
+                    _ => coalesced
+                        .push(Update::UpdateModified(incident, (u_old, u_new))),
+                }
+            },
+        }
+    }
+
+    coalesced
+}
+
 #[derive(Debug)]
 pub enum Update {
     Created(Incident),
     // Deleted(String), // TODO: do i have a way to find this?
     UpdateCreated(Incident, IncidentUpdate),
     UpdateModified(Incident, (IncidentUpdate, IncidentUpdate)),
+    PostmortemPublished(Incident, IncidentUpdate),
+}
+
+impl Update {
+    pub fn incident(&self) -> &Incident {
+        match self {
+            Self::Created(i)
+            | Self::UpdateCreated(i, _)
+            | Self::UpdateModified(i, _)
+            | Self::PostmortemPublished(i, _) => i,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -220,6 +493,16 @@ pub struct Incident {
 
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub monitoring_at: Option<DateTime<Utc>>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+impl Incident {
+    /// When the incident started, which can be backdated from the dashboard
+    pub fn start_time(&self) -> DateTime<Utc> {
+        self.started_at.unwrap_or(self.created_at)
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -231,6 +514,15 @@ pub struct IncidentUpdate {
     pub affected_components: Option<Vec<AffectedComponent>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub display_at: Option<DateTime<Utc>>,
+}
+
+impl IncidentUpdate {
+    /// The time shown on the status page, which can be changed from the
+    /// dashboard after the update is posted
+    pub fn display_time(&self) -> DateTime<Utc> {
+        self.display_at.unwrap_or(self.created_at)
+    }
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
@@ -243,16 +535,15 @@ pub enum IncidentStatus {
     Postmortem,
 }
 
-impl ToString for IncidentStatus {
-    fn to_string(&self) -> String {
-        match self {
+impl fmt::Display for IncidentStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
             Self::Identified => "Identified",
             Self::Investigating => "Investigating",
             Self::Monitoring => "Monitoring",
             Self::Resolved => "Resolved",
             Self::Postmortem => "Postmortem",
-        }
-        .to_string()
+        })
     }
 }
 
@@ -266,6 +557,19 @@ pub enum StatusIndicator {
     Maintenance,
 }
 
+impl StatusIndicator {
+    /// The name the status page uses for this impact
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Minor => "minor",
+            Self::Major => "major",
+            Self::Critical => "critical",
+            Self::Maintenance => "maintenance",
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum ComponentStatus {
@@ -276,6 +580,19 @@ pub enum ComponentStatus {
     UnderMaintenance,
 }
 
+impl ComponentStatus {
+    /// The name the status page uses for this status
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Operational => "operational",
+            Self::DegradedPerformance => "degraded_performance",
+            Self::PartialOutage => "partial_outage",
+            Self::MajorOutage => "major_outage",
+            Self::UnderMaintenance => "under_maintenance",
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct AffectedComponent {
     pub code: String,
@@ -284,6 +601,7 @@ pub struct AffectedComponent {
     pub new_status: ComponentStatus,
 }
 
+#[derive(Clone, Debug, Deserialize)]
 pub struct Component {
     pub id: String,
     pub name: String,
@@ -292,3 +610,257 @@ pub struct Component {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Summary {
+    pub status: PageStatus,
+    pub components: Vec<Component>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PageStatus {
+    pub indicator: StatusIndicator,
+
+    /// Like "All Systems Operational"
+    pub description: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use chrono::TimeZone;
+    use futures::StreamExt;
+    use serde_json::json;
+    use tokio::sync::broadcast;
+
+    use super::*;
+    use crate::{replay::Replay, sinks::testing::TestServer};
+
+    /// A new incident being investigated, identified with its update
+    /// edited afterwards, resolved, and getting a postmortem the next day
+    const RECORDING: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/incidents.jsonl"
+    );
+
+    /// Fails the first fetch, then plays back the recording
+    struct Flaky {
+        failed: AtomicBool,
+        replay: Replay,
+    }
+
+    impl IncidentSource for Flaky {
+        fn get_all_incidents(
+            &self,
+        ) -> BoxFuture<'_, Result<Incidents, SourceError>> {
+            if self.failed.swap(true, Ordering::SeqCst) {
+                return self.replay.get_all_incidents();
+            }
+
+            let source = ReqwestClient::new().get("not a url").build();
+            async move { Err(source.unwrap_err().into()) }.boxed()
+        }
+    }
+
+    async fn load_recording() -> Vec<Incidents> {
+        let replay = Replay::load(RECORDING).unwrap();
+        let mut snapshots = vec![];
+
+        while let Ok(incidents) = replay.get_all_incidents().await {
+            snapshots.push(incidents);
+        }
+
+        snapshots
+    }
+
+    fn describe(update: &Update) -> String {
+        match update {
+            Update::Created(i) => format!("created {}", i.id),
+            Update::UpdateCreated(_, u) => format!("update {}", u.id),
+            Update::UpdateModified(_, (_, u)) => format!("modified {}", u.id),
+            Update::PostmortemPublished(_, u) => format!("postmortem {}", u.id),
+        }
+    }
+
+    fn update(id: &str, body: &str) -> IncidentUpdate {
+        IncidentUpdate {
+            id: id.to_string(),
+            incident_id: "incident".to_string(),
+            status: IncidentStatus::Investigating,
+            body: body.to_string(),
+            affected_components: None,
+            created_at: Utc.timestamp_opt(0, 0).unwrap(),
+            updated_at: Utc.timestamp_opt(0, 0).unwrap(),
+            display_at: None,
+        }
+    }
+
+    /// An incident with its updates newest first, like the status page
+    fn incident(updates: Vec<IncidentUpdate>) -> Incident {
+        Incident {
+            id: "incident".to_string(),
+            name: "Something broke".to_string(),
+            shortlink: "https://stspg.io/incident".to_string(),
+            incident_updates: updates,
+            status: IncidentStatus::Investigating,
+            impact: StatusIndicator::Minor,
+            created_at: Utc.timestamp_opt(0, 0).unwrap(),
+            updated_at: Utc.timestamp_opt(0, 0).unwrap(),
+            started_at: None,
+            monitoring_at: None,
+            resolved_at: None,
+        }
+    }
+
+    fn update_ids(update: &Update) -> Vec<&str> {
+        update
+            .incident()
+            .incident_updates
+            .iter()
+            .map(|u| u.id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn updates_after_a_new_incident_are_kept() {
+        let first = update("first", "Looking into it");
+        let second = update("second", "Found it");
+        let latest = incident(vec![second.clone(), first.clone()]);
+
+        let coalesced = coalesce_updates(vec![
+            vec![Update::Created(incident(vec![first]))],
+            vec![Update::UpdateCreated(latest.clone(), second)],
+        ]);
+
+        assert_eq!(coalesced.len(), 2);
+        assert!(matches!(&coalesced[0], Update::Created(_)));
+        assert_eq!(update_ids(&coalesced[0]), ["first"]);
+        assert!(
+            matches!(&coalesced[1], Update::UpdateCreated(_, u) if u.id == "second")
+        );
+        assert_eq!(update_ids(&coalesced[1]), ["second", "first"]);
+    }
+
+    #[test]
+    fn new_incidents_get_the_latest_version_of_their_updates() {
+        let first = update("first", "Looking into it");
+        let edited = update("first", "Looking into it, again");
+
+        let coalesced = coalesce_updates(vec![
+            vec![Update::Created(incident(vec![first.clone()]))],
+            vec![Update::UpdateModified(
+                incident(vec![edited.clone()]),
+                (first, edited),
+            )],
+        ]);
+
+        assert_eq!(coalesced.len(), 1);
+        assert_eq!(
+            coalesced[0].incident().incident_updates[0].body,
+            "Looking into it, again"
+        );
+    }
+
+    #[tokio::test]
+    async fn recording_changes() {
+        let snapshots = load_recording().await;
+        let changes: Vec<Vec<_>> = snapshots
+            .windows(2)
+            .map(|w| {
+                StatuspageUpdatesPoll::cmp_incidents(&w[0], &w[1])
+                    .iter()
+                    .map(describe)
+                    .collect()
+            })
+            .collect();
+
+        assert_eq!(
+            changes,
+            [
+                vec![],
+                vec!["created x2tpl4hz8b1k"],
+                vec!["update lq4d7v9hzr5m"],
+                vec!["modified lq4d7v9hzr5m"],
+                vec!["update 7yw3g8n2kq0c"],
+                vec!["postmortem b1x6rj0dwm9s"],
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn recording_coalesced() {
+        let snapshots = load_recording().await;
+        let batches = snapshots
+            .windows(2)
+            .map(|w| StatuspageUpdatesPoll::cmp_incidents(&w[0], &w[1]))
+            .filter(|changes| !changes.is_empty())
+            .collect();
+
+        let coalesced = coalesce_updates(batches);
+
+        // the edit is part of the update it was made to
+        assert_eq!(
+            coalesced.iter().map(describe).collect::<Vec<_>>(),
+            [
+                "created x2tpl4hz8b1k",
+                "update lq4d7v9hzr5m",
+                "update 7yw3g8n2kq0c",
+                "postmortem b1x6rj0dwm9s",
+            ]
+        );
+        assert!(matches!(
+            &coalesced[1],
+            Update::UpdateCreated(_, u) if u.body.contains("voice server deployment")
+        ));
+        assert_eq!(update_ids(&coalesced[0]), ["f0bcg1ffz3wd"]);
+    }
+
+    #[tokio::test]
+    async fn initial_fetch_is_retried() {
+        let source = Flaky {
+            failed: AtomicBool::new(false),
+            replay: Replay::load(RECORDING).unwrap(),
+        };
+        let (updates, poll) = StatuspageUpdates::new(
+            Box::new(source),
+            16,
+            Duration::from_millis(1),
+            Arc::new(Metrics::new()),
+        );
+        let (_stop_tx, stop_rx) = broadcast::channel(1);
+
+        // the stream ends once the replay is finished
+        let poll = tokio::spawn(async move { poll.start(stop_rx).await });
+        let found: Vec<_> =
+            updates.flat_map(futures::stream::iter).collect().await;
+        poll.await.unwrap();
+
+        // how many batches get merged depends on timing, but everything
+        // after the first snapshot is found
+        let found: Vec<_> = found.iter().map(describe).collect();
+        assert_eq!(found.first().unwrap(), "created x2tpl4hz8b1k");
+        assert_eq!(found.last().unwrap(), "postmortem b1x6rj0dwm9s");
+    }
+
+    #[tokio::test]
+    async fn error_pages_are_status_errors() {
+        let server = TestServer::start();
+        let api = StatuspageAPI::new(server.url.clone());
+
+        server.respond(503, json!("<html>Service Unavailable</html>"));
+        let err = api.get_all_incidents().await.unwrap_err();
+        assert_eq!(
+            err.status(),
+            Some(reqwest::StatusCode::SERVICE_UNAVAILABLE)
+        );
+
+        // a successful response that isn't what was expected is a decoding
+        // error instead
+        server.respond(200, json!({ "page": {} }));
+        let err = api.get_all_incidents().await.unwrap_err();
+        assert!(err.is_decode());
+
+        assert_eq!(server.requests()[0].path, "/api/v2/incidents.json");
+    }
+}
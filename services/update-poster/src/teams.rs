@@ -0,0 +1,209 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{
+    branding::Branding,
+    embeds::POSTMORTEM_EXCERPT_MAX_LEN,
+    statuspage::{Incident, IncidentStatus, IncidentUpdate},
+    text::{get_update_heading, TextStyle},
+    util::{
+        get_embed_color,
+        get_excerpt,
+        get_formatted_utc_timestamp,
+        get_links,
+        get_timeline_updates,
+        truncate_with_ellipsis,
+    },
+};
+
+/// Teams rejects messages over about 28KB, so long incidents are cut short
+const BODY_MAX_LEN: usize = 2000;
+const MAX_UPDATES: usize = 10;
+
+/// A message with a single Adaptive Card
+/// https://learn.microsoft.com/en-us/microsoftteams/platform/webhooks-and-connectors/how-to/connectors-using#send-adaptive-cards-using-an-incoming-webhook
+#[derive(Clone, Debug, Serialize)]
+pub struct TeamsMessage {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub attachments: Vec<Attachment>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    pub content_type: &'static str,
+
+    /// https://adaptivecards.io/explorer/AdaptiveCard.html
+    pub content: Value,
+}
+
+impl TeamsMessage {
+    pub fn post(
+        incident: &Incident,
+        update: &IncidentUpdate,
+        branding: &Branding,
+    ) -> Self {
+        Self::build(
+            incident,
+            branding,
+            &update.status,
+            vec![make_update(update, branding, &update.body)],
+            "Started",
+        )
+    }
+
+    pub fn edit(incident: &Incident, branding: &Branding) -> Self {
+        let updates = get_timeline_updates(incident)
+            .into_iter()
+            .take(MAX_UPDATES)
+            .rev()
+            .map(|upd| make_update(upd, branding, &upd.body))
+            .collect();
+
+        Self::build(incident, branding, &incident.status, updates, "Started")
+    }
+
+    pub fn postmortem(
+        incident: &Incident,
+        update: &IncidentUpdate,
+        branding: &Branding,
+    ) -> Self {
+        let excerpt = get_excerpt(&update.body, POSTMORTEM_EXCERPT_MAX_LEN);
+
+        Self::build(
+            incident,
+            branding,
+            &update.status,
+            vec![make_update(update, branding, &excerpt)],
+            "Postmortem published",
+        )
+    }
+
+    /// Cards can't have a coloured bar like embeds, so the header's
+    /// background is coloured instead
+    fn build(
+        incident: &Incident,
+        branding: &Branding,
+        status: &IncidentStatus,
+        updates: Vec<Value>,
+        footer: &str,
+    ) -> Self {
+        let color = get_embed_color(branding, incident, status);
+
+        let mut body = vec![json!({
+            "type": "Container",
+            "style": get_container_style(color),
+            "bleed": true,
+            "items": [
+                {
+                    "type": "TextBlock",
+                    "text": incident.name,
+                    "size": "Large",
+                    "weight": "Bolder",
+                    "wrap": true,
+                },
+                {
+                    "type": "TextBlock",
+                    "text": branding.author.name,
+                    "size": "Small",
+                    "isSubtle": true,
+                    "spacing": "None",
+                },
+            ],
+        })];
+        body.extend(updates);
+        body.push(json!({
+            "type": "TextBlock",
+            "text": format!(
+                "{} {}",
+                footer,
+                get_formatted_utc_timestamp(&incident.start_time())
+            ),
+            "size": "Small",
+            "isSubtle": true,
+            "separator": true,
+        }));
+
+        let actions: Vec<_> = get_links(incident, branding)
+            .into_iter()
+            .map(|(title, url)| {
+                json!({
+                    "type": "Action.OpenUrl",
+                    "title": title,
+                    "url": url,
+                })
+            })
+            .collect();
+
+        Self {
+            kind: "message",
+            attachments: vec![Attachment {
+                content_type: "application/vnd.microsoft.card.adaptive",
+                content: json!({
+                    "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                    "type": "AdaptiveCard",
+                    "version": "1.4",
+                    "msteams": { "width": "Full" },
+                    "body": body,
+                    "actions": actions,
+                }),
+            }],
+        }
+    }
+}
+
+fn make_update(
+    update: &IncidentUpdate,
+    branding: &Branding,
+    body: &str,
+) -> Value {
+    json!({
+        "type": "Container",
+        "separator": true,
+        "items": [
+            {
+                "type": "TextBlock",
+                "text": get_update_heading(update, branding, TextStyle::Markdown),
+                "wrap": true,
+            },
+            {
+                "type": "TextBlock",
+                "text": truncate_with_ellipsis(body.to_string(), BODY_MAX_LEN),
+                "wrap": true,
+                "spacing": "Small",
+            },
+        ],
+    })
+}
+
+/// Cards can only use a few named colours, so this picks the one closest in
+/// hue to an embed colour. Greys use the default style
+/// https://adaptivecards.io/explorer/Container.html
+fn get_container_style(color: u32) -> &'static str {
+    let [r, g, b] = [(color >> 16) & 0xFF, (color >> 8) & 0xFF, color & 0xFF]
+        .map(|c| c as f32 / 255.0);
+
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    if max == 0.0 || delta / max < 0.2 {
+        return "default";
+    }
+
+    let hue = if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+
+    match hue as u32 {
+        0..=14 | 330..=360 => "attention",
+        15..=69 => "warning",
+        70..=169 => "good",
+        _ => "accent",
+    }
+}
@@ -0,0 +1,124 @@
+use serde::Serialize;
+
+use crate::{
+    branding::Branding,
+    embeds::POSTMORTEM_EXCERPT_MAX_LEN,
+    statuspage::{Incident, IncidentUpdate},
+    text::{get_update_heading, TextStyle},
+    util::{
+        escape_html,
+        fit_timeline,
+        get_excerpt,
+        get_formatted_utc_timestamp,
+        get_links,
+        get_timeline_updates,
+        truncate_with_ellipsis,
+    },
+};
+
+/// Telegram rejects messages over 4096 characters after parsing the HTML.
+/// This leaves room for the incident's name and the footer, and counting the
+/// tags leaves some more
+const UPDATES_MAX_LEN: usize = 3500;
+const BODY_MAX_LEN: usize = 1000;
+
+/// A message with `parse_mode` set to `HTML`, which only supports a few tags
+/// and keeps newlines as they are
+/// https://core.telegram.org/bots/api#html-style
+#[derive(Clone, Debug, Serialize)]
+pub struct TelegramMessage {
+    pub text: String,
+
+    /// Shown under the message as an inline keyboard
+    pub buttons: Vec<Button>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Button {
+    pub text: &'static str,
+    pub url: String,
+}
+
+impl TelegramMessage {
+    pub fn post(
+        incident: &Incident,
+        update: &IncidentUpdate,
+        branding: &Branding,
+    ) -> Self {
+        Self::build(
+            incident,
+            branding,
+            vec![make_update_html(update, branding, &update.body)],
+        )
+    }
+
+    pub fn edit(incident: &Incident, branding: &Branding) -> Self {
+        let updates = fit_timeline(
+            get_timeline_updates(incident)
+                .into_iter()
+                .map(|upd| make_update_html(upd, branding, &upd.body)),
+            UPDATES_MAX_LEN,
+        );
+
+        Self::build(incident, branding, updates)
+    }
+
+    pub fn postmortem(
+        incident: &Incident,
+        update: &IncidentUpdate,
+        branding: &Branding,
+    ) -> Self {
+        let excerpt = get_excerpt(&update.body, POSTMORTEM_EXCERPT_MAX_LEN);
+
+        Self::build(
+            incident,
+            branding,
+            vec![format!(
+                "{}\n\n<a href=\"{}\">Read the full postmortem</a>",
+                make_update_html(update, branding, &excerpt),
+                escape_html(&incident.shortlink),
+            )],
+        )
+    }
+
+    fn build(
+        incident: &Incident,
+        branding: &Branding,
+        updates: Vec<String>,
+    ) -> Self {
+        let header = format!(
+            "<b><a href=\"{}\">{}</a></b>",
+            escape_html(&incident.shortlink),
+            escape_html(&incident.name),
+        );
+        let footer = format!(
+            "<i>{} · Started {}</i>",
+            escape_html(&branding.author.name),
+            get_formatted_utc_timestamp(&incident.start_time()),
+        );
+
+        let buttons = get_links(incident, branding)
+            .into_iter()
+            .map(|(text, url)| Button { text, url })
+            .collect();
+
+        Self {
+            text: [vec![header], updates, vec![footer]].concat().join("\n\n"),
+            buttons,
+        }
+    }
+}
+
+/// Like [`crate::text::make_update_html`], but without the `<p>` and `<br>`
+/// tags Telegram doesn't support
+fn make_update_html(
+    update: &IncidentUpdate,
+    branding: &Branding,
+    body: &str,
+) -> String {
+    format!(
+        "{}\n{}",
+        get_update_heading(update, branding, TextStyle::Html),
+        escape_html(&truncate_with_ellipsis(body.to_string(), BODY_MAX_LEN)),
+    )
+}
@@ -0,0 +1,295 @@
+use chrono::{DateTime, Utc};
+
+use crate::{
+    branding::Branding,
+    embeds::POSTMORTEM_EXCERPT_MAX_LEN,
+    statuspage::{Incident, IncidentStatus, IncidentUpdate},
+    util::{
+        escape_html,
+        get_excerpt,
+        get_formatted_utc_timestamp,
+        get_status_emoji,
+        get_timeline_updates,
+        get_unicode_status_emoji,
+        truncate_with_ellipsis,
+    },
+};
+
+/// The maximum length of a Discord message's content
+pub const CONTENT_MAX_LEN: usize = 2000;
+
+/// How the text is marked up
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextStyle {
+    /// Discord's markdown, with its custom emoji
+    Discord,
+
+    /// No markup at all, like the plain text parts of emails
+    Plain,
+
+    /// The markdown Teams' text blocks support
+    Markdown,
+
+    /// Slack's `mrkdwn`, which shows dates in the reader's timezone
+    Slack,
+
+    /// The few tags every sink that takes HTML supports. The text functions
+    /// don't escape bodies, so it's only for [`make_update_html`]
+    Html,
+}
+
+impl TextStyle {
+    fn bold(self, s: &str) -> String {
+        match self {
+            Self::Discord | Self::Markdown => format!("**{}**", s),
+            Self::Slack => format!("*{}*", s),
+            Self::Html => format!("<b>{}</b>", escape_html(s)),
+            Self::Plain => s.to_string(),
+        }
+    }
+
+    fn italic(self, s: &str) -> String {
+        match self {
+            Self::Discord | Self::Markdown | Self::Slack => format!("_{}_", s),
+            Self::Html => format!("<i>{}</i>", escape_html(s)),
+            Self::Plain => s.to_string(),
+        }
+    }
+
+    /// Discord shows a preview below links unless they're in angle brackets
+    fn link(self, url: &str) -> String {
+        match self {
+            Self::Discord | Self::Slack => format!("<{}>", url),
+            Self::Html => format!("<a href=\"{0}\">{0}</a>", escape_html(url)),
+            Self::Markdown | Self::Plain => url.to_string(),
+        }
+    }
+
+    /// Only Discord can show its custom emoji
+    fn emoji(self, branding: &Branding, status: &IncidentStatus) -> String {
+        match self {
+            Self::Discord => get_status_emoji(branding, status).to_string(),
+            Self::Html => {
+                escape_html(get_unicode_status_emoji(branding, status))
+            },
+            _ => get_unicode_status_emoji(branding, status).to_string(),
+        }
+    }
+
+    pub fn timestamp(self, time: &DateTime<Utc>) -> String {
+        match self {
+            // falls back to UTC where the date can't be shown
+            // https://api.slack.com/reference/surfaces/formatting#date-formatting
+            Self::Slack => format!(
+                "<!date^{}^{{date_short_pretty}} {{time}}|{}>",
+                time.timestamp(),
+                get_formatted_utc_timestamp(time),
+            ),
+            _ => get_formatted_utc_timestamp(time),
+        }
+    }
+}
+
+fn get_header(incident: &Incident, style: TextStyle) -> String {
+    style.bold(&incident.name) + "\n"
+}
+
+fn get_footer(incident: &Incident, style: TextStyle) -> String {
+    format!("\n{}", style.link(&incident.shortlink))
+}
+
+/// An update's emoji, status and time, which goes above its body
+pub fn get_update_heading(
+    update: &IncidentUpdate,
+    branding: &Branding,
+    style: TextStyle,
+) -> String {
+    format!(
+        "{} {} · {}",
+        style.emoji(branding, &update.status),
+        style.bold(&update.status.to_string()),
+        style.timestamp(&update.display_time()),
+    )
+}
+
+/// An update as an HTML paragraph. The body is cut short to `max_len` bytes
+/// before it's escaped, so entities are never split
+pub fn make_update_html(
+    update: &IncidentUpdate,
+    branding: &Branding,
+    max_len: usize,
+) -> String {
+    let body = truncate_with_ellipsis(update.body.clone(), max_len);
+
+    format!(
+        "<p>{}<br>{}</p>",
+        get_update_heading(update, branding, TextStyle::Html),
+        escape_html(&body).replace('\n', "<br>"),
+    )
+}
+
+/// Renders a single update as text, fitting in `max_len` bytes
+pub fn make_post_text(
+    incident: &Incident,
+    update: &IncidentUpdate,
+    branding: &Branding,
+    style: TextStyle,
+    max_len: usize,
+) -> String {
+    let header = get_header(incident, style)
+        + &get_update_heading(update, branding, style)
+        + "\n";
+    let footer = get_footer(incident, style);
+
+    let body_len = max_len.saturating_sub(header.len() + footer.len());
+
+    truncate_with_ellipsis(
+        header
+            + &truncate_with_ellipsis(update.body.clone(), body_len)
+            + &footer,
+        max_len,
+    )
+}
+
+/// Renders an incident's whole timeline as text, fitting in `max_len`
+/// bytes. The oldest updates are dropped first when there isn't enough room.
+pub fn make_edit_text(
+    incident: &Incident,
+    branding: &Branding,
+    style: TextStyle,
+    max_len: usize,
+) -> String {
+    let header = get_header(incident, style);
+    let footer = get_footer(incident, style);
+    let updates = get_timeline_updates(incident);
+
+    // leave room for the note about hidden updates, which is longest when
+    // all but one are hidden
+    let note_len = match updates.len() {
+        0 | 1 => 0,
+        n => get_hidden_note(n - 1, style).len(),
+    };
+    let budget = max_len.saturating_sub(header.len() + footer.len() + note_len);
+
+    let mut sections: Vec<String> = vec![];
+    let mut len = 0;
+
+    for upd in &updates {
+        let heading = get_update_heading(upd, branding, style) + "\n";
+        let section = heading.clone() + &upd.body + "\n";
+
+        // sections are joined with a newline
+        let section_len = section.len() + 1;
+
+        if len + section_len <= budget {
+            len += section_len;
+            sections.push(section);
+        } else {
+            // always show the latest update, even if it has to be cut short
+            if sections.is_empty() {
+                let body_len = budget.saturating_sub(heading.len() + 1);
+                sections.push(
+                    heading
+                        + &truncate_with_ellipsis(upd.body.clone(), body_len)
+                        + "\n",
+                );
+            }
+            break;
+        }
+    }
+
+    let hidden = updates.len() - sections.len();
+    let mut text = header;
+
+    if hidden > 0 {
+        text += &get_hidden_note(hidden, style);
+    }
+
+    text += &sections.into_iter().rev().collect::<Vec<_>>().join("\n");
+    text += &footer;
+
+    truncate_with_ellipsis(text, max_len)
+}
+
+fn get_hidden_note(hidden: usize, style: TextStyle) -> String {
+    style.italic(&format!("({hidden} earlier updates not shown)")) + "\n"
+}
+
+/// Renders a postmortem excerpt as text, fitting in `max_len` bytes
+pub fn make_postmortem_text(
+    incident: &Incident,
+    update: &IncidentUpdate,
+    branding: &Branding,
+    style: TextStyle,
+    max_len: usize,
+) -> String {
+    let text = format!(
+        "{}{}\n{}\n\nRead the full postmortem: {}",
+        get_header(incident, style),
+        get_update_heading(update, branding, style),
+        get_excerpt(&update.body, POSTMORTEM_EXCERPT_MAX_LEN),
+        style.link(&incident.shortlink),
+    );
+
+    truncate_with_ellipsis(text, max_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{branding::EmojiSet, sinks::testing::incident};
+
+    #[test]
+    fn plain_text_has_no_markup() {
+        let incident = incident();
+        let branding = Branding {
+            emoji: EmojiSet {
+                resolved: Some("<:statusgreen:1234>".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let discord =
+            make_edit_text(&incident, &branding, TextStyle::Discord, 4000);
+        assert!(discord.starts_with("**Voice Connection Failures**\n"));
+        assert!(discord.contains("<:statusgreen:1234> **Resolved**"));
+        assert!(discord.ends_with("\n<https://stspg.io/x2tpl4>"));
+
+        let plain =
+            make_edit_text(&incident, &branding, TextStyle::Plain, 4000);
+        assert!(plain.starts_with("Voice Connection Failures\n"));
+        assert!(plain.contains("🟢 Resolved"));
+        assert!(plain.ends_with("\nhttps://stspg.io/x2tpl4"));
+        assert!(!plain.contains("**") && !plain.contains('<'));
+    }
+
+    #[test]
+    fn edit_text_keeps_footer() {
+        let mut incident = incident();
+        let latest = get_timeline_updates(&incident)[0].clone();
+
+        incident.incident_updates = (0..150)
+            .map(|i| IncidentUpdate {
+                id: format!("update{}", i),
+                body: "x".repeat(100 + i as usize),
+                created_at: latest.created_at - chrono::Duration::minutes(i),
+                ..latest.clone()
+            })
+            .collect();
+
+        let branding = Branding::default();
+        for max_len in [500, 1000, 2000, 4000] {
+            let text = make_edit_text(
+                &incident,
+                &branding,
+                TextStyle::Discord,
+                max_len,
+            );
+
+            assert!(text.len() <= max_len);
+            assert!(text.contains("earlier updates not shown)_\n"));
+            assert!(text.ends_with("\n<https://stspg.io/x2tpl4>"), "{}", text);
+        }
+    }
+}
@@ -1,39 +1,213 @@
-use chrono::{DateTime, Utc};
+use std::cmp::Reverse;
 
-use crate::{constants::*, statuspage::IncidentStatus};
+use chrono::{DateTime, Utc};
 
-pub fn get_embed_color(status: &IncidentStatus) -> u32 {
-    use IncidentStatus::*;
+use crate::{
+    branding::{Branding, ColorBy},
+    statuspage::{Incident, IncidentStatus, IncidentUpdate},
+};
 
-    match status {
-        Investigating => EMBED_ORANGE,
-        Identified => EMBED_RED,
-        Monitoring => EMBED_YELLOW,
-        Resolved => EMBED_GREEN,
-        Postmortem => EMBED_BLUE,
+pub fn get_embed_color(
+    branding: &Branding,
+    incident: &Incident,
+    status: &IncidentStatus,
+) -> u32 {
+    match branding.color_by {
+        ColorBy::Status => branding.colors.status.get(status),
+        ColorBy::Impact => branding.colors.impact.get(&incident.impact),
     }
 }
 
-pub fn get_status_emoji(status: &IncidentStatus) -> &'static str {
-    use IncidentStatus::*;
+pub fn get_status_emoji<'a>(
+    branding: &'a Branding,
+    status: &IncidentStatus,
+) -> &'a str {
+    branding.emoji.get(status)
+}
 
-    match status {
-        Investigating => EMOJI_ORANGE,
-        Identified => EMOJI_RED,
-        Monitoring => EMOJI_YELLOW,
-        Resolved => EMOJI_GREEN,
-        Postmortem => EMOJI_BLUE,
-    }
+/// The status emoji for sinks outside Discord, which can't show its custom
+/// emoji
+pub fn get_unicode_status_emoji<'a>(
+    branding: &'a Branding,
+    status: &IncidentStatus,
+) -> &'a str {
+    branding.emoji.get_unicode(status)
 }
 
 pub fn get_formatted_timestamp(time: &DateTime<Utc>) -> String {
     format!("<t:{}:R>", time.timestamp())
 }
 
+/// Formats a timestamp as plain text, for places that can't render Discord's
+/// `<t:...>` markup
+pub fn get_formatted_utc_timestamp(time: &DateTime<Utc>) -> String {
+    time.format("%b %-d, %H:%M UTC").to_string()
+}
+
+/// The links shown as buttons below an incident's messages, as labels and
+/// URLs. They all go to the incident's page, which is also where the status
+/// page shows the postmortem and offers subscriptions
+pub fn get_links(
+    incident: &Incident,
+    branding: &Branding,
+) -> Vec<(&'static str, String)> {
+    if !branding.buttons.enabled {
+        return vec![];
+    }
+
+    let mut links = vec![("View on status page", incident.shortlink.clone())];
+
+    if incident
+        .incident_updates
+        .iter()
+        .any(|u| u.status == IncidentStatus::Postmortem)
+    {
+        links.push(("Postmortem", incident.shortlink.clone()));
+    }
+
+    if branding.buttons.subscribe {
+        links.push(("Subscribe to this incident", incident.shortlink.clone()));
+    }
+
+    links
+}
+
+/// Returns the updates shown in an incident's timeline, newest first.
+/// Postmortems are left out since they're sent as their own message.
+pub fn get_timeline_updates(incident: &Incident) -> Vec<&IncidentUpdate> {
+    let mut updates = incident
+        .incident_updates
+        .iter()
+        .filter(|upd| upd.status != IncidentStatus::Postmortem)
+        .collect::<Vec<_>>();
+    updates.sort_by_key(|upd| Reverse(upd.display_time()));
+
+    updates
+}
+
+/// Keeps the newest of `updates`, which are rendered newest first, that fit
+/// in `max_len` bytes together, and puts them oldest first. The newest is
+/// kept even if it doesn't fit, so it should be cut short already
+pub fn fit_timeline(
+    updates: impl IntoIterator<Item = String>,
+    max_len: usize,
+) -> Vec<String> {
+    let mut len = 0;
+    let mut fitting: Vec<_> = updates
+        .into_iter()
+        .enumerate()
+        .take_while(|(i, update)| {
+            len += update.len();
+            *i == 0 || len <= max_len
+        })
+        .map(|(_, update)| update)
+        .collect();
+    fitting.reverse();
+
+    fitting
+}
+
+/// Cuts `s` down to at most `len` bytes, ending with an ellipsis unless
+/// there's no room for one
 pub fn truncate_with_ellipsis(s: String, len: usize) -> String {
-    if s.len() > len {
-        s[..=len - 3].to_string() + "..."
-    } else {
-        s
+    if s.len() <= len {
+        return s;
+    }
+
+    let ellipsis = if len < 3 { "" } else { "..." };
+    let mut end = len - ellipsis.len();
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    s[..end].to_string() + ellipsis
+}
+
+/// Returns the first paragraph of `s`, truncated to at most `len` bytes
+pub fn get_excerpt(s: &str, len: usize) -> String {
+    let paragraph = s
+        .split("\n\n")
+        .map(str::trim)
+        .find(|p| !p.is_empty())
+        .unwrap_or_default();
+
+    truncate_with_ellipsis(paragraph.to_string(), len)
+}
+
+/// Escapes text for use in HTML
+pub fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::testing::incident;
+
+    #[test]
+    fn truncate_keeps_short_strings() {
+        assert_eq!(truncate_with_ellipsis("abc".to_string(), 3), "abc");
+        assert_eq!(truncate_with_ellipsis(String::new(), 0), "");
+    }
+
+    #[test]
+    fn truncate_adds_ellipsis() {
+        assert_eq!(truncate_with_ellipsis("abcdef".to_string(), 5), "ab...");
+        assert_eq!(truncate_with_ellipsis("abcdef".to_string(), 3), "...");
+    }
+
+    #[test]
+    fn truncate_without_room_for_ellipsis() {
+        assert_eq!(truncate_with_ellipsis("abcdef".to_string(), 2), "ab");
+        assert_eq!(truncate_with_ellipsis("abcdef".to_string(), 0), "");
+    }
+
+    #[test]
+    fn truncate_on_char_boundary() {
+        // "é" is two bytes, so cutting after 4 bytes would split it
+        assert_eq!(truncate_with_ellipsis("aaééé".to_string(), 6), "aa...");
+        assert_eq!(truncate_with_ellipsis("éé".to_string(), 1), "");
+    }
+
+    #[test]
+    fn timeline_keeps_newest_that_fit() {
+        let updates = ["ccc", "bbb", "aaa"].map(String::from);
+
+        assert_eq!(fit_timeline(updates.clone(), 9), ["aaa", "bbb", "ccc"]);
+        assert_eq!(fit_timeline(updates.clone(), 8), ["bbb", "ccc"]);
+        assert_eq!(fit_timeline(updates, 1), ["ccc"]);
+    }
+
+    #[test]
+    fn links_for_every_button() {
+        let mut incident = incident();
+        let mut branding = Branding::default();
+
+        let labels = |incident: &Incident, branding: &Branding| {
+            get_links(incident, branding)
+                .into_iter()
+                .map(|(label, _)| label)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            labels(&incident, &branding),
+            ["View on status page", "Postmortem"]
+        );
+
+        branding.buttons.subscribe = true;
+        incident
+            .incident_updates
+            .retain(|u| u.status != IncidentStatus::Postmortem);
+        assert_eq!(
+            labels(&incident, &branding),
+            ["View on status page", "Subscribe to this incident"]
+        );
+
+        branding.buttons.enabled = false;
+        assert!(get_links(&incident, &branding).is_empty());
     }
 }
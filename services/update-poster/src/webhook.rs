@@ -0,0 +1,196 @@
+//! The JSON sent to generic webhooks. Fields are only ever added within a
+//! version; anything that would break existing consumers bumps
+//! [`SCHEMA_VERSION`].
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Serializer};
+
+use crate::statuspage::{
+    AffectedComponent,
+    Incident,
+    IncidentStatus,
+    IncidentUpdate,
+    StatusIndicator,
+    Update,
+};
+
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    IncidentCreated,
+    UpdateCreated,
+    UpdateModified,
+    PostmortemPublished,
+
+    /// The incident's current state, when there's no change to describe.
+    /// Not currently delivered, since the reconciler is the only thing that
+    /// renders notifications without one
+    IncidentUpdated,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::IncidentCreated => "incident.created",
+            Self::UpdateCreated => "incident_update.created",
+            Self::UpdateModified => "incident_update.modified",
+            Self::PostmortemPublished => "postmortem.published",
+            Self::IncidentUpdated => "incident.updated",
+        }
+    }
+}
+
+impl Serialize for EventKind {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// One event, without anything that changes between deliveries of it
+#[derive(Clone, Debug, Serialize)]
+pub struct WebhookEvent {
+    pub version: u32,
+    pub event: EventKind,
+    pub incident: EventIncident,
+
+    /// The incident update the event is about, if it's about one
+    pub update: Option<EventUpdate>,
+
+    /// The fields of `update` that changed, for `incident_update.modified`
+    pub diff: Option<UpdateDiff>,
+
+    /// The incident's impact, repeated here since it's what most consumers
+    /// route on
+    pub impact: StatusIndicator,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct EventIncident {
+    pub id: String,
+    pub name: String,
+    pub status: IncidentStatus,
+    pub impact: StatusIndicator,
+    pub shortlink: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub started_at: DateTime<Utc>,
+    pub monitoring_at: Option<DateTime<Utc>>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct EventUpdate {
+    pub id: String,
+    pub status: IncidentStatus,
+    pub body: String,
+    pub affected_components: Vec<AffectedComponent>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub display_at: DateTime<Utc>,
+}
+
+/// Fields that can be changed on an update after it's posted. Only the ones
+/// that changed are set
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct UpdateDiff {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<Change<IncidentStatus>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<Change<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_at: Option<Change<DateTime<Utc>>>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Change<T> {
+    pub old: T,
+    pub new: T,
+}
+
+impl WebhookEvent {
+    /// The event for `update`, or the incident's current state if there
+    /// isn't one
+    pub fn new(incident: &Incident, update: Option<&Update>) -> Self {
+        let (event, upd, diff) = match update {
+            Some(Update::Created(i)) => {
+                (EventKind::IncidentCreated, i.incident_updates.first(), None)
+            },
+            Some(Update::UpdateCreated(_, u)) => {
+                (EventKind::UpdateCreated, Some(u), None)
+            },
+            Some(Update::UpdateModified(_, (old, new))) => (
+                EventKind::UpdateModified,
+                Some(new),
+                Some(UpdateDiff::new(old, new)),
+            ),
+            Some(Update::PostmortemPublished(_, u)) => {
+                (EventKind::PostmortemPublished, Some(u), None)
+            },
+            None => (EventKind::IncidentUpdated, None, None),
+        };
+
+        Self {
+            version: SCHEMA_VERSION,
+            event,
+            incident: EventIncident::from(incident),
+            update: upd.map(EventUpdate::from),
+            diff,
+            impact: incident.impact,
+        }
+    }
+}
+
+impl From<&Incident> for EventIncident {
+    fn from(incident: &Incident) -> Self {
+        Self {
+            id: incident.id.clone(),
+            name: incident.name.clone(),
+            status: incident.status,
+            impact: incident.impact,
+            shortlink: incident.shortlink.clone(),
+            created_at: incident.created_at,
+            updated_at: incident.updated_at,
+            started_at: incident.start_time(),
+            monitoring_at: incident.monitoring_at,
+            resolved_at: incident.resolved_at,
+        }
+    }
+}
+
+impl From<&IncidentUpdate> for EventUpdate {
+    fn from(update: &IncidentUpdate) -> Self {
+        Self {
+            id: update.id.clone(),
+            status: update.status,
+            body: update.body.clone(),
+            affected_components: update
+                .affected_components
+                .clone()
+                .unwrap_or_default(),
+            created_at: update.created_at,
+            updated_at: update.updated_at,
+            display_at: update.display_time(),
+        }
+    }
+}
+
+impl UpdateDiff {
+    fn new(old: &IncidentUpdate, new: &IncidentUpdate) -> Self {
+        fn change<T: Clone + PartialEq>(old: &T, new: &T) -> Option<Change<T>> {
+            (old != new).then(|| Change {
+                old: old.clone(),
+                new: new.clone(),
+            })
+        }
+
+        Self {
+            status: change(&old.status, &new.status),
+            body: change(&old.body, &new.body),
+            display_at: change(&old.display_time(), &new.display_time()),
+        }
+    }
+}